@@ -0,0 +1,893 @@
+//! A single simulated aircraft
+
+use serde::Deserialize;
+use svc_atc_client_rest::types::*;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use svc_telemetry_client_rest::netrid_types::*;
+
+use crate::backend::{CargoScanner, OrderSource, TelemetrySink};
+use crate::battery::{self, Battery, BatteryOptions};
+use crate::clock::{Participant, SimClock};
+use crate::control::Command;
+use crate::fleet::{AircraftStatus, StatusBoard};
+use crate::kinematics::KinematicLimits;
+use crate::link::telemetry::drop_frame;
+use crate::link::{CargoMessage, LinkOptions, LinkSettings, Links, OrderMessage, Snapshot, TelemetryMessage};
+use crate::logging::{RateLimiter, TICK_LOG_INTERVAL_MS};
+use crate::metrics::metrics;
+use crate::orders::{self, ParcelScan};
+use crate::phase::{self, PhaseOptions};
+use crate::profile::{self, PerformanceProfile};
+use crate::retry::RetryPolicy;
+use crate::scenario::Rates;
+use crate::shutdown::{Landing, Shutdown, ShutdownMode, ShutdownOptions, ShutdownReport};
+use crate::telemetry::*;
+use crate::wind::Wind;
+
+pub use crate::phase::Activity;
+
+pub struct State {
+    pub current_plan: Option<FlightPlan>,
+    pub id: String,
+    pub scanner_id: String,
+    pub activity: Activity,
+    /// time the current phase was entered
+    pub phase_since_ms: u64,
+    /// altitude the current phase was entered at
+    pub phase_altitude_m: f64,
+    pub position: PointZ,
+    /// over the ground, the air velocity plus the wind
+    pub ground_velocity_m_s: f64,
+    pub vertical_velocity_m_s: f64,
+    /// direction of travel over the ground
+    pub track_angle_deg: f64,
+    /// speed through the air
+    pub airspeed_m_s: f64,
+    /// direction the aircraft points at, into the wind of the track
+    pub heading_deg: f64,
+    /// previous point of the current plan, the leg runs from there to the
+    ///  next point
+    pub leg_origin: Option<PointZ>,
+    /// cruise speed of the current plan, `ground_velocity_m_s` ramps
+    ///  towards it
+    pub target_ground_velocity_m_s: f64,
+    pub acceleration_m_s2: f64,
+    pub vertical_acceleration_m_s2: f64,
+    pub last_update_ms: u64,
+    pub last_id_update_ms: u64,
+    pub last_order_check: u64,
+    /// for simulating sudden out of service
+    pub operational: bool,
+    /// movement and plan changes are frozen
+    pub paused: bool,
+    /// cargo ids of the parcels picked up and not delivered yet
+    pub onboard: Vec<String>,
+    /// mass of the parcels on board
+    pub payload_kg: f64,
+    /// not simulated without battery options
+    pub battery: Option<Battery>,
+    /// landing short of the target of the current plan, see
+    ///  [`crate::battery`]
+    pub diverted: bool,
+    /// limits messages logged on every tick
+    pub tick_log: RateLimiter,
+}
+
+impl State {
+    /// An idle aircraft on the ground at `position`
+    pub fn new(id: String, scanner_id: String, position: PointZ) -> Self {
+        State {
+            id,
+            scanner_id,
+            current_plan: None,
+            activity: Activity::Idle,
+            phase_since_ms: 0,
+            phase_altitude_m: position.altitude_meters,
+            position,
+            ground_velocity_m_s: 0.0,
+            vertical_velocity_m_s: 0.0,
+            track_angle_deg: 0.0,
+            airspeed_m_s: 0.0,
+            heading_deg: 0.0,
+            leg_origin: None,
+            target_ground_velocity_m_s: 0.0,
+            acceleration_m_s2: 0.0,
+            vertical_acceleration_m_s2: 0.0,
+            last_update_ms: 0,
+            last_id_update_ms: 0,
+            last_order_check: 0,
+            operational: true,
+            paused: false,
+            onboard: vec![],
+            payload_kg: 0.0,
+            battery: None,
+            diverted: false,
+            tick_log: RateLimiter::new(TICK_LOG_INTERVAL_MS),
+        }
+    }
+
+    /// Come to a halt and hover in place
+    pub fn stop(&mut self) {
+        self.ground_velocity_m_s = 0.0;
+        self.airspeed_m_s = 0.0;
+        self.vertical_velocity_m_s = 0.0;
+        self.target_ground_velocity_m_s = 0.0;
+        self.acceleration_m_s2 = 0.0;
+        self.vertical_acceleration_m_s2 = 0.0;
+    }
+}
+
+/// Identity and starting position of a single aircraft
+#[derive(Debug, Clone, Deserialize)]
+pub struct AircraftConfig {
+    pub name: String,
+    pub uuid: String,
+    pub scanner_id: String,
+    pub longitude: f64,
+    pub latitude: f64,
+    /// ANSI/CTA-2063-A serial number, sent as the UAS ID while no flight
+    ///  plan is active instead of the name
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    /// name of the performance profile of the scenario, the default
+    ///  profile without one
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// A simulated aircraft connected to its backends
+///
+/// [`Aircraft::run`] drives the aircraft in real time (or fast-forward,
+///  depending on the clock). Test harnesses can drive it themselves with
+///  [`Aircraft::advance`] and [`Aircraft::step`].
+///
+/// A tick only moves the aircraft and queues messages; the backends are
+///  called by the link tasks spawned on the first step, see [`crate::link`].
+///
+/// Once the [`Shutdown`] it was given fires, [`Aircraft::run`] lands the
+///  aircraft and returns its final state, see [`crate::shutdown`].
+pub struct Aircraft<T, O, C> {
+    pub state: State,
+    /// Flight plans waiting for their origin timeslot
+    pub plans: Vec<FlightPlan>,
+    uuid: String,
+    serial_number: Option<String>,
+    clock: SimClock,
+    /// steps a fast-forward clock together with the rest of the fleet
+    _turn: Participant,
+    rates: Rates,
+    limits: KinematicLimits,
+    phases: PhaseOptions,
+    profile: PerformanceProfile,
+    battery: Option<BatteryOptions>,
+    wind: Wind,
+    /// backends handed to the links when they are spawned
+    backends: Option<(T, O, C)>,
+    links: Option<Links>,
+    /// flight plans pushed to the aircraft and the poll interval while
+    ///  the feed is open, polling is the fallback
+    order_feed: Option<(mpsc::Receiver<FlightPlan>, u64)>,
+    retry: RetryPolicy,
+    link_options: LinkOptions,
+    /// commands from the control API
+    commands: Option<mpsc::UnboundedReceiver<Command>>,
+    shutdown: Option<Shutdown>,
+    /// set once shutting down, no flight plans are accepted anymore
+    closing: Option<ShutdownOptions>,
+    /// flight plans declined while shutting down
+    declined: usize,
+    last_tick: u64,
+}
+
+impl<T, O, C> Aircraft<T, O, C>
+where
+    T: TelemetrySink,
+    O: OrderSource,
+    C: CargoScanner,
+{
+    pub fn new(
+        config: AircraftConfig,
+        clock: SimClock,
+        rates: Rates,
+        telemetry: T,
+        orders: O,
+        cargo: C,
+    ) -> Self {
+        let state = State::new(
+            config.name,
+            config.scanner_id.replace('"', ""),
+            PointZ {
+                longitude: config.longitude,
+                latitude: config.latitude,
+                altitude_meters: 0.0,
+            },
+        );
+
+        Aircraft {
+            state,
+            plans: vec![],
+            uuid: config.uuid,
+            serial_number: config.serial_number,
+            last_tick: clock.now_ms(),
+            _turn: clock.join(),
+            clock,
+            rates,
+            limits: KinematicLimits::default(),
+            phases: PhaseOptions::default(),
+            profile: PerformanceProfile::default(),
+            battery: None,
+            wind: Wind::default(),
+            backends: Some((telemetry, orders, cargo)),
+            links: None,
+            order_feed: None,
+            retry: RetryPolicy::default(),
+            link_options: LinkOptions::default(),
+            commands: None,
+            shutdown: None,
+            closing: None,
+            declined: 0,
+        }
+    }
+
+    /// Also take flight plans pushed to `rx`
+    ///
+    /// While the feed is open orders are only polled every
+    ///  `poll_interval_ms`; once it closes polling resumes at the configured
+    ///  rate.
+    pub fn with_order_feed(mut self, rx: mpsc::Receiver<FlightPlan>, poll_interval_ms: u64) -> Self {
+        self.order_feed = Some((rx, poll_interval_ms));
+        self
+    }
+
+    /// Back off from unavailable services according to `policy`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Ramp speed, climb and heading within `limits`
+    pub fn with_kinematic_limits(mut self, limits: KinematicLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Fly the phases of each plan according to `options`
+    pub fn with_phase_options(mut self, options: PhaseOptions) -> Self {
+        self.phases = options;
+        self
+    }
+
+    /// Fly within the performance of `profile` and report its UA type
+    pub fn with_profile(mut self, profile: PerformanceProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Start with a full battery and draw power according to `options`
+    pub fn with_battery(mut self, options: BatteryOptions) -> Self {
+        self.state.battery = Some(Battery::full(options.capacity_wh));
+        self.battery = Some(options);
+        self
+    }
+
+    /// Drift with `wind` in the air
+    pub fn with_wind(mut self, wind: Wind) -> Self {
+        self.wind = wind;
+        self
+    }
+
+    /// Use `options` for the timeouts and queues of the links
+    pub fn with_link_options(mut self, options: LinkOptions) -> Self {
+        self.link_options = options;
+        self
+    }
+
+    /// Take commands from `rx`, applied at the start of each tick
+    pub fn with_commands(mut self, rx: mpsc::UnboundedReceiver<Command>) -> Self {
+        self.commands = Some(rx);
+        self
+    }
+
+    /// Land and stop [`Aircraft::run`] once `shutdown` fires
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Continue from `status`, saved by [`crate::checkpoint`]
+    ///
+    /// Restores the position, activity, the current plan with its remaining
+    ///  path and the leg being flown, the queued plans, the parcels on board
+    ///  and, when simulated, the battery.
+    pub fn with_restored(mut self, status: AircraftStatus) -> Self {
+        self.state.position = status.position;
+        phase::reset(&mut self.state, status.activity, self.clock.now_ms());
+        if let Some(phase_altitude_m) = status.phase_altitude_m {
+            self.state.phase_altitude_m = phase_altitude_m;
+        }
+        self.state.leg_origin = status.leg_origin;
+        self.state.operational = status.operational;
+        self.state.paused = status.paused;
+        self.state.ground_velocity_m_s = status.ground_velocity_m_s;
+        self.state.vertical_velocity_m_s = status.vertical_velocity_m_s;
+        self.state.track_angle_deg = status.track_angle_deg;
+        self.state.airspeed_m_s = status.airspeed_m_s;
+        self.state.heading_deg = status.heading_deg;
+        self.state.target_ground_velocity_m_s = status.target_ground_velocity_m_s;
+        self.state.current_plan = status.current_plan;
+        self.state.onboard = status.onboard;
+        self.state.payload_kg = status.payload_kg;
+        self.state.diverted = status.diverted;
+        if self.state.battery.is_some() && status.battery.is_some() {
+            self.state.battery = status.battery;
+        }
+        self.plans = status.queued_plans;
+        self
+    }
+
+    /// Uuid of the aircraft in the backend
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// Current simulation time of this aircraft
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    /// Move a stepped clock forward by one step
+    pub fn advance(&mut self) {
+        self.clock.advance();
+    }
+
+    /// Run the simulation loop until the shutdown fires, or until the task
+    ///  is dropped without one
+    pub async fn run(self, tick_ms: u64, status: StatusBoard) -> ShutdownReport {
+        let span = tracing::info_span!("aircraft", name = %self.state.id, uuid = %self.uuid);
+        self.run_loop(tick_ms, status).instrument(span).await
+    }
+
+    async fn run_loop(mut self, tick_ms: u64, status: StatusBoard) -> ShutdownReport {
+        tracing::info!(clock = %self.clock, "aircraft startup");
+
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(tick_ms));
+        loop {
+            if self.shutdown.as_ref().is_some_and(Shutdown::requested) {
+                return self.shut_down(&mut interval, &status).await;
+            }
+
+            self.next_tick(&mut interval).await;
+            self.step().await;
+            self.publish_status(&status);
+        }
+    }
+
+    /// Wait for the next tick
+    async fn next_tick(&mut self, interval: &mut tokio::time::Interval) {
+        if self.clock.is_stepped() {
+            // fast-forward: no real time sleeps, only wait for the rest of
+            //  the fleet and let other tasks run
+            self.clock.next_step().await;
+            tokio::task::yield_now().await;
+        } else {
+            interval.tick().await;
+        }
+    }
+
+    /// Stop taking flight plans, get to the ground and report the final
+    ///  position to svc-telemetry
+    async fn shut_down(
+        &mut self,
+        interval: &mut tokio::time::Interval,
+        status: &StatusBoard,
+    ) -> ShutdownReport {
+        let options = self.shutdown.as_ref().map(|s| s.options).unwrap_or_default();
+        tracing::info!(mode = ?options.mode, queued = self.plans.len(), "shutting down");
+
+        self.start();
+        self.closing = Some(options);
+        for plan in std::mem::take(&mut self.plans) {
+            self.refuse_plan(plan);
+        }
+
+        // still on the pad, no need to take off
+        if !self.state.activity.is_airborne() {
+            self.abort_plan();
+        }
+
+        let airborne = self.state.activity.is_airborne();
+        let landing = match (&self.state.current_plan, options.mode) {
+            (None, _) if !airborne => Landing::Idle,
+            (None, ShutdownMode::Fly) => {
+                // holding without a plan, come down where it is
+                phase::transition(&mut self.state, Activity::Landing, self.clock.now_ms());
+                match self.fly_to_target(interval, status, options.max_fly_ms).await {
+                    Landing::Arrived => Landing::InPlace,
+                    landing => landing,
+                }
+            }
+            (Some(_), ShutdownMode::Fly) => {
+                self.fly_to_target(interval, status, options.max_fly_ms).await
+            }
+            (_, ShutdownMode::Teleport) => {
+                self.teleport_to_target();
+                Landing::Teleported
+            }
+        };
+
+        let flushed = self.report_landed(options.flush_timeout_ms).await;
+        self.publish_status(status);
+
+        ShutdownReport {
+            name: self.state.id.clone(),
+            landing,
+            position: self.state.position.clone(),
+            declined: self.declined,
+            flushed,
+        }
+    }
+
+    /// Keep flying the current plan until it ends and the aircraft is on
+    ///  the ground, teleport to its target if that takes longer than
+    ///  `max_fly_ms` of wall clock time
+    async fn fly_to_target(
+        &mut self,
+        interval: &mut tokio::time::Interval,
+        status: &StatusBoard,
+        max_fly_ms: u64,
+    ) -> Landing {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(max_fly_ms);
+        while self.state.current_plan.is_some() || self.state.activity.is_airborne() {
+            if std::time::Instant::now() >= deadline {
+                tracing::warn!(max_fly_ms, "flight plan not finished in time, teleporting");
+                self.teleport_to_target();
+                return Landing::Teleported;
+            }
+
+            self.next_tick(interval).await;
+            self.step().await;
+            self.publish_status(status);
+        }
+
+        Landing::Arrived
+    }
+
+    /// Move to the ground at the target of the current plan and drop the
+    ///  plan without delivering its parcels
+    ///
+    /// Without a plan the aircraft moves to the ground below it.
+    fn teleport_to_target(&mut self) {
+        let target = self.state.current_plan.as_ref().and_then(|plan| plan.path.last());
+        let (latitude, longitude) = match target {
+            Some(target) => (target.latitude, target.longitude),
+            None => (self.state.position.latitude, self.state.position.longitude),
+        };
+
+        self.state.position = PointZ {
+            latitude,
+            longitude,
+            altitude_meters: 0.0,
+        };
+        self.abort_plan();
+        self.state.stop();
+        phase::reset(&mut self.state, Activity::Idle, self.clock.now_ms());
+    }
+
+    /// Send a final Location frame and wait for the links to handle it,
+    ///  along with the declined plans
+    ///
+    /// Returns false if that took longer than `timeout_ms`.
+    async fn report_landed(&mut self, timeout_ms: u64) -> bool {
+        let Some(ref links) = self.links else {
+            return false;
+        };
+
+        let report = async {
+            match location_frame(&self.state, self.clock.now()) {
+                Ok(frame) => {
+                    let _ = links.telemetry.send(TelemetryMessage::Frame(frame)).await;
+                }
+                Err(e) => {
+                    count_encoding_error(FrameKind::Location);
+                    tracing::error!(error = %e, "could not encode final position");
+                }
+            }
+
+            links.flush().await;
+        };
+
+        let timeout = tokio::time::Duration::from_millis(timeout_ms);
+        match tokio::time::timeout(timeout, report).await {
+            Ok(()) => true,
+            Err(_) => {
+                tracing::warn!(timeout_ms, "backends did not confirm the final position in time");
+                false
+            }
+        }
+    }
+
+    /// Process one tick at the current simulation time
+    ///
+    /// With a stepped clock the step also waits for the links to handle
+    ///  what the tick queued, so simulation time does not run ahead of the
+    ///  backends. With a real time clock it never waits on the network.
+    pub async fn step(&mut self) {
+        self.start();
+
+        let span = match self.state.current_plan {
+            Some(ref plan) => tracing::info_span!("plan", session = %plan.session_id),
+            None => tracing::Span::none(),
+        };
+
+        span.in_scope(|| self.tick());
+
+        if self.clock.is_stepped() {
+            self.flush().await;
+        }
+    }
+
+    /// Wait until the links handled every message queued so far
+    ///
+    /// Acknowledgements and parcel scans waiting for a backoff stay queued.
+    pub async fn flush(&self) {
+        if let Some(ref links) = self.links {
+            links.flush().await;
+        }
+    }
+
+    /// Spawn the links, in the current span, unless they run already
+    fn start(&mut self) {
+        let Some((telemetry, orders, cargo)) = self.backends.take() else {
+            return;
+        };
+
+        let settings = LinkSettings {
+            identifier: self.state.id.clone(),
+            uuid: self.uuid.clone(),
+            scanner_id: self.state.scanner_id.clone(),
+            rates: self.rates,
+            retry: self.retry,
+            options: self.link_options,
+            order_feed: self.order_feed.take(),
+        };
+
+        self.links = Some(Links::spawn(settings, telemetry, orders, cargo, self.snapshot()));
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            now_ms: self.clock.now_ms(),
+            operational: self.state.operational,
+            accepting_orders: self.closing.is_none(),
+        }
+    }
+
+    fn tick(&mut self) {
+        self.apply_commands();
+
+        let current_tick = self.clock.now_ms();
+        if !self.state.paused {
+            phase::advance(current_tick, &mut self.state, &self.phases, &self.profile);
+            update_location(
+                &current_tick,
+                &self.last_tick,
+                &mut self.state,
+                &self.profile.limits(&self.limits),
+                &self.phases,
+                &mut self.wind,
+            );
+            if let Some(ref options) = self.battery {
+                battery::update(current_tick, self.last_tick, &mut self.state, options, &self.phases);
+            }
+        }
+        self.last_tick = current_tick;
+
+        if let Some(ref links) = self.links {
+            links.snapshots.send_replace(self.snapshot());
+        }
+
+        // out of service aircraft are silent
+        if !self.state.operational {
+            return;
+        }
+
+        // Check for new orders
+        let ready = self.state.activity.accepts_plan();
+        if self.state.current_plan.is_none() && ready && !self.state.paused {
+            let mut activate = false;
+            if let Some(fp) = self.plans.first() {
+                if (fp.origin_timeslot_end.timestamp_millis() as u64) < current_tick {
+                    activate = true;
+                }
+            }
+
+            if activate {
+                let plan = self.plans.remove(0);
+                if self.can_fly(&plan, current_tick) {
+                    let scans = orders::init_plan(
+                        &self.clock,
+                        &mut self.state,
+                        current_tick,
+                        plan,
+                        &self.profile,
+                    );
+                    self.queue_scans(scans);
+                } else {
+                    self.decline_plan(plan);
+                }
+            }
+        }
+
+        if let Some(ref plan) = self.state.current_plan {
+            if plan.path.is_empty() && !self.state.paused {
+                let scans = orders::end_plan(&self.clock, &mut self.state, &self.phases);
+                self.queue_scans(scans);
+            }
+        }
+
+        self.report_telemetry(current_tick);
+        self.receive_orders();
+    }
+
+    /// Queue the NETRID frames that are due for the telemetry link
+    fn report_telemetry(&mut self, current_tick: u64) {
+        // Every 2000ms (0.5 Hz) by default
+        let since_id_update_ms = current_tick.saturating_sub(self.state.last_id_update_ms);
+        if since_id_update_ms > self.rates.id_interval_ms {
+            let (id_type, id) = match (&self.state.current_plan, &self.serial_number) {
+                (Some(p), _) => (IdType::SpecificSession, p.session_id.clone()),
+                (None, Some(serial)) => (IdType::SerialNumber, serial.clone()),
+                (None, None) => (IdType::CaaAssigned, self.state.id.clone())
+            };
+
+            // issue id update
+            let ua_type = self.profile.airframe.ua_type();
+            match id_frame(&self.state.id, ua_type, id_type, &id, self.clock.now()) {
+                Ok(frame) => self.queue_frame(frame),
+                Err(e) => {
+                    // try again at the next interval
+                    count_encoding_error(FrameKind::Basic);
+                    tracing::warn!(error = %e, "skipping id update");
+                }
+            }
+            self.state.last_id_update_ms = current_tick;
+        }
+
+        // Every 500ms (2 Hz) by default
+        let since_update_ms = current_tick.saturating_sub(self.state.last_update_ms);
+        if since_update_ms > self.rates.position_interval_ms {
+            // issue position and velocity update
+            match location_frame(&self.state, self.clock.now()) {
+                Ok(frame) => self.queue_frame(frame),
+                Err(e) => {
+                    // try again at the next interval
+                    count_encoding_error(FrameKind::Location);
+                    tracing::warn!(error = %e, "skipping position update");
+                }
+            }
+            self.state.last_update_ms = current_tick;
+        }
+    }
+
+    /// Hand a frame to the telemetry link, dropping it if the queue is full
+    fn queue_frame(&self, frame: TelemetryFrame) {
+        let Some(ref links) = self.links else {
+            return;
+        };
+
+        if let Err(e) = links.telemetry.try_send(TelemetryMessage::Frame(frame)) {
+            if let TelemetryMessage::Frame(ref frame) = e.into_inner() {
+                drop_frame(frame);
+            }
+        }
+    }
+
+    /// Hand parcel scans to the cargo link
+    fn queue_scans(&self, scans: Vec<ParcelScan>) {
+        let Some(ref links) = self.links else {
+            return;
+        };
+
+        for scan in scans {
+            // the link only ends once the aircraft is dropped
+            let _ = links.cargo.send(CargoMessage::Scan(scan));
+        }
+    }
+
+    /// Merge the flight plans received by the order link and have it
+    ///  acknowledge them, or refuse them while shutting down
+    fn receive_orders(&mut self) {
+        let Some(ref mut links) = self.links else {
+            return;
+        };
+
+        let mut received = vec![];
+        while let Ok(plan) = links.plans.try_recv() {
+            received.push(plan);
+        }
+
+        if received.is_empty() {
+            return;
+        }
+
+        if self.closing.is_some() {
+            for plan in received {
+                self.refuse_plan(plan);
+            }
+            return;
+        }
+
+        for flight_id in orders::merge_orders(&mut self.plans, received) {
+            let _ = links.orders.send(OrderMessage::Acknowledge(flight_id));
+        }
+    }
+
+    /// If the profile allows `plan` and the battery covers it when started
+    ///  at `now_ms`
+    fn can_fly(&self, plan: &FlightPlan, now_ms: u64) -> bool {
+        if let Err(e) = profile::check(plan, &self.state, now_ms, &self.profile, &self.phases) {
+            tracing::warn!(session = %plan.session_id, reason = %e, "flight plan cannot be flown");
+            return false;
+        }
+
+        match self.battery {
+            Some(ref options) => {
+                battery::can_fly(plan, &self.state, now_ms, options, &self.phases, &self.profile)
+            }
+            None => true,
+        }
+    }
+
+    /// Decline a plan the aircraft cannot fly
+    fn decline_plan(&self, plan: FlightPlan) {
+        tracing::info!(session = %plan.session_id, "declining flight plan");
+        if let Some(ref links) = self.links {
+            let _ = links.orders.send(OrderMessage::Decline(plan.flight_uuid));
+        }
+    }
+
+    /// Drop a plan while shutting down, declining it if configured
+    fn refuse_plan(&mut self, plan: FlightPlan) {
+        let decline = self.closing.is_some_and(|options| options.decline_queued);
+        let Some(ref links) = self.links else {
+            return;
+        };
+
+        if !decline {
+            tracing::info!(session = %plan.session_id, "dropping flight plan");
+            return;
+        }
+
+        tracing::info!(session = %plan.session_id, "declining flight plan");
+        if links.orders.send(OrderMessage::Decline(plan.flight_uuid)).is_ok() {
+            self.declined += 1;
+        }
+    }
+
+    /// Apply the commands received since the last tick
+    fn apply_commands(&mut self) {
+        let Some(ref mut rx) = self.commands else {
+            return;
+        };
+
+        let mut commands = vec![];
+        while let Ok(command) = rx.try_recv() {
+            commands.push(command);
+        }
+
+        for command in commands {
+            tracing::info!(?command, "applying command");
+            match command {
+                Command::Pause => self.state.paused = true,
+                Command::Resume => self.state.paused = false,
+                Command::AbortPlan => self.abort_plan(),
+                Command::Teleport {
+                    latitude,
+                    longitude,
+                    altitude_meters,
+                } => {
+                    self.state.position = PointZ {
+                        latitude,
+                        longitude,
+                        altitude_meters,
+                    };
+
+                    // without a plan, hover or stand where it was put
+                    if self.state.current_plan.is_none() && self.state.operational {
+                        let next = self.resting_phase();
+                        phase::reset(&mut self.state, next, self.clock.now_ms());
+                    }
+                }
+                Command::OutOfService => {
+                    self.abort_plan();
+                    self.state.operational = false;
+                    phase::transition(&mut self.state, Activity::OutOfService, self.clock.now_ms());
+                }
+                Command::ReturnToService => {
+                    self.state.operational = true;
+                    let next = self.resting_phase();
+                    phase::transition(&mut self.state, next, self.clock.now_ms());
+                    // log in again, the old session may have expired; no
+                    //  frames were queued while out of service
+                    if let Some(ref links) = self.links {
+                        if let Err(e) = links.telemetry.try_send(TelemetryMessage::Relogin) {
+                            // the link still logs in again once the token is rejected
+                            tracing::warn!(error = %e, "could not ask the link to log in again");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Current state of the aircraft, as published on the status board
+    pub fn status(&self) -> AircraftStatus {
+        AircraftStatus {
+            timestamp: self.clock.now(),
+            activity: self.state.activity,
+            operational: self.state.operational,
+            paused: self.state.paused,
+            position: self.state.position.clone(),
+            ground_velocity_m_s: self.state.ground_velocity_m_s,
+            vertical_velocity_m_s: self.state.vertical_velocity_m_s,
+            track_angle_deg: self.state.track_angle_deg,
+            airspeed_m_s: self.state.airspeed_m_s,
+            heading_deg: self.state.heading_deg,
+            target_ground_velocity_m_s: self.state.target_ground_velocity_m_s,
+            phase_altitude_m: Some(self.state.phase_altitude_m),
+            leg_origin: self.state.leg_origin.clone(),
+            current_plan: self.state.current_plan.clone(),
+            queued_plans: self.plans.clone(),
+            onboard: self.state.onboard.clone(),
+            payload_kg: self.state.payload_kg,
+            battery: self.state.battery,
+            diverted: self.state.diverted,
+        }
+    }
+
+    /// Record the current state of the aircraft on the fleet status board
+    fn publish_status(&self, status: &StatusBoard) {
+        let Ok(mut board) = status.lock() else {
+            tracing::error!("fleet status board is poisoned");
+            return;
+        };
+
+        board.insert(self.state.id.clone(), self.status());
+    }
+
+    /// Phase of the aircraft without a plan at its current altitude
+    fn resting_phase(&self) -> Activity {
+        if self.state.position.altitude_meters > phase::TOUCHDOWN_M {
+            Activity::Hold
+        } else {
+            Activity::Idle
+        }
+    }
+
+    /// Drop the current plan without delivering its parcels
+    ///
+    /// In the air the aircraft holds in place, otherwise it stays on the
+    ///  ground.
+    fn abort_plan(&mut self) {
+        let Some(plan) = self.state.current_plan.take() else {
+            return;
+        };
+
+        tracing::info!(session = %plan.session_id, "aborted flight plan");
+        self.state.stop();
+        let next = if self.state.activity.is_airborne() {
+            Activity::Hold
+        } else {
+            Activity::Idle
+        };
+        phase::transition(&mut self.state, next, self.clock.now_ms());
+    }
+}
+
+fn count_encoding_error(kind: FrameKind) {
+    metrics()
+        .frame_encoding_errors
+        .with_label_values(&[format!("{:?}", kind).as_str()])
+        .inc();
+}
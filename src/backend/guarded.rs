@@ -0,0 +1,103 @@
+//! Circuit breakers in front of any backend
+
+use std::future::Future;
+use std::time::Duration;
+use svc_atc_client_rest::types::FlightPlan;
+use svc_cargo_client_rest::types::CargoScan;
+
+use super::{CargoScanner, OrderSource, TelemetrySink};
+use crate::error::BackendError;
+use crate::link::LinkOptions;
+use crate::retry::Breakers;
+use crate::telemetry::TelemetryFrame;
+
+/// Backend whose calls go through the circuit breaker of their endpoint
+///
+/// Calls to an endpoint with an open breaker fail right away with
+///  [`BackendError::CircuitOpen`]. Calls that take longer than the timeout
+///  of their link fail with [`BackendError::Timeout`], and count against
+///  the breaker like any other failure. Cheap to clone; clones share the
+///  breakers.
+#[derive(Debug, Clone)]
+pub struct Guarded<B> {
+    inner: B,
+    breakers: Breakers,
+    timeouts: LinkOptions,
+}
+
+impl<B> Guarded<B> {
+    pub fn new(inner: B, breakers: Breakers) -> Self {
+        Guarded {
+            inner,
+            breakers,
+            timeouts: LinkOptions::default(),
+        }
+    }
+
+    /// Time out calls after the timeouts of `options`
+    pub fn with_timeouts(mut self, options: LinkOptions) -> Self {
+        self.timeouts = options;
+        self
+    }
+
+    async fn call<T>(
+        &self,
+        endpoint: &'static str,
+        timeout_ms: u64,
+        request: impl Future<Output = Result<T, BackendError>>,
+    ) -> Result<T, BackendError> {
+        if !self.breakers.allow(endpoint) {
+            return Err(BackendError::CircuitOpen { endpoint });
+        }
+
+        // a hung service is a failure too, record it before giving up
+        let result = tokio::time::timeout(Duration::from_millis(timeout_ms), request)
+            .await
+            .unwrap_or(Err(BackendError::Timeout { endpoint }));
+        self.breakers.record(endpoint, &result);
+        result
+    }
+}
+
+impl<B: TelemetrySink> TelemetrySink for Guarded<B> {
+    async fn acquire_token(&self, identifier: &str) -> Result<String, BackendError> {
+        let timeout_ms = self.timeouts.telemetry_timeout_ms;
+        self.call("login", timeout_ms, self.inner.acquire_token(identifier)).await
+    }
+
+    async fn send_frame(&self, token: &str, frame: &TelemetryFrame) -> Result<(), BackendError> {
+        let timeout_ms = self.timeouts.telemetry_timeout_ms;
+        self.call("netrid", timeout_ms, self.inner.send_frame(token, frame)).await
+    }
+}
+
+impl<B: OrderSource> OrderSource for Guarded<B> {
+    async fn get_orders(
+        &self,
+        aircraft_uuid: &str,
+        identifier: &str,
+    ) -> Result<Vec<FlightPlan>, BackendError> {
+        let timeout_ms = self.timeouts.orders_timeout_ms;
+        self.call("plans", timeout_ms, self.inner.get_orders(aircraft_uuid, identifier))
+            .await
+    }
+
+    async fn acknowledge_order(&self, flight_id: &str, identifier: &str) -> Result<(), BackendError> {
+        let timeout_ms = self.timeouts.orders_timeout_ms;
+        self.call("acknowledge", timeout_ms, self.inner.acknowledge_order(flight_id, identifier))
+            .await
+    }
+
+    async fn decline_order(&self, flight_id: &str, identifier: &str) -> Result<(), BackendError> {
+        let timeout_ms = self.timeouts.orders_timeout_ms;
+        self.call("decline", timeout_ms, self.inner.decline_order(flight_id, identifier))
+            .await
+    }
+}
+
+impl<B: CargoScanner> CargoScanner for Guarded<B> {
+    async fn parcel_scan(&self, identifier: &str, scan: CargoScan) -> Result<(), BackendError> {
+        let timeout_ms = self.timeouts.cargo_timeout_ms;
+        self.call("scan", timeout_ms, self.inner.parcel_scan(identifier, scan)).await
+    }
+}
@@ -0,0 +1,463 @@
+//! Backend talking to svc-telemetry, svc-atc and svc-cargo over HTTP
+
+use hyper::{
+    body::{Body, Bytes},
+    client::connect::{Connect, HttpConnector},
+    client::Client,
+    Method, Request, StatusCode,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use rustls::{ClientConfig, RootCertStore};
+use serde::Deserialize;
+use std::io::BufReader;
+use std::time::{Duration, Instant};
+use svc_atc_client_rest::types::*;
+use svc_cargo_client_rest::types::*;
+
+use super::{CargoScanner, OrderSource, TelemetrySink};
+use crate::error::BackendError;
+use crate::metrics::metrics;
+use crate::telemetry::TelemetryFrame;
+
+/// Connector of the client built by [`client`], for `http` and `https` URIs
+pub type Connector = HttpsConnector<HttpConnector>;
+
+/// Base URIs of the backend services
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub tlm_uri: String,
+    pub atc_uri: String,
+    pub cargo_uri: String,
+}
+
+/// HTTP client options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HttpOptions {
+    /// timeout of establishing a connection, including the TLS handshake
+    pub connect_timeout_ms: u64,
+    /// timeout of a request, from sending it to reading the whole response
+    pub request_timeout_ms: u64,
+    /// idle connections kept per host
+    pub pool_max_idle_per_host: usize,
+    /// idle connections are closed after this
+    pub pool_idle_timeout_ms: u64,
+    /// speak HTTP/2 without negotiating it first
+    pub http2_prior_knowledge: bool,
+    /// reach the services over `https`
+    pub https: bool,
+    /// PEM file with the CA certificates trusted for `https`, instead of
+    ///  the bundled Mozilla roots
+    pub ca_bundle: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            connect_timeout_ms: 2000,
+            request_timeout_ms: 10_000,
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout_ms: 10_000,
+            http2_prior_knowledge: false,
+            https: false,
+            ca_bundle: None,
+        }
+    }
+}
+
+/// Why the HTTP client could not be built
+#[derive(Debug)]
+pub enum ClientError {
+    /// the CA bundle could not be read
+    Io(String, std::io::Error),
+    /// the CA bundle holds no certificate
+    NoCertificates(String),
+    /// a certificate of the CA bundle was rejected
+    Certificate(String, rustls::Error),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Io(path, e) => write!(f, "could not read CA bundle {}: {}", path, e),
+            ClientError::NoCertificates(path) => write!(f, "no certificates in CA bundle {}", path),
+            ClientError::Certificate(path, e) => {
+                write!(f, "invalid certificate in CA bundle {}: {}", path, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(_, e) => Some(e),
+            ClientError::NoCertificates(_) => None,
+            ClientError::Certificate(_, e) => Some(e),
+        }
+    }
+}
+
+/// Build an HTTP client according to `options`
+///
+/// The client speaks `http` and `https`; `options.https` only decides the
+///  scheme of the service URIs.
+pub fn client(options: &HttpOptions) -> Result<Client<Connector>, ClientError> {
+    let roots = match options.ca_bundle {
+        Some(ref path) => load_ca_bundle(path)?,
+        None => {
+            let mut roots = RootCertStore::empty();
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            roots
+        }
+    };
+
+    let tls = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(Some(Duration::from_millis(options.connect_timeout_ms)));
+
+    let builder = HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1();
+    let connector = if options.http2_prior_knowledge {
+        builder.enable_http2().wrap_connector(http)
+    } else {
+        builder.wrap_connector(http)
+    };
+
+    let client = Client::builder()
+        .pool_idle_timeout(Duration::from_millis(options.pool_idle_timeout_ms))
+        .pool_max_idle_per_host(options.pool_max_idle_per_host)
+        .http2_only(options.http2_prior_knowledge)
+        .build(connector);
+
+    Ok(client)
+}
+
+/// Read the CA certificates of a PEM file
+fn load_ca_bundle(path: &str) -> Result<RootCertStore, ClientError> {
+    let file = std::fs::File::open(path).map_err(|e| ClientError::Io(path.to_string(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| ClientError::Io(path.to_string(), e))?;
+
+    if certs.is_empty() {
+        return Err(ClientError::NoCertificates(path.to_string()));
+    }
+
+    let mut roots = RootCertStore::empty();
+    for cert in certs {
+        roots
+            .add(&rustls::Certificate(cert))
+            .map_err(|e| ClientError::Certificate(path.to_string(), e))?;
+    }
+
+    Ok(roots)
+}
+
+/// HTTP client for the telemetry, atc and cargo services
+///
+/// Cheap to clone; clones share the same connection pool.
+#[derive(Debug, Clone)]
+pub struct HttpBackend<C = Connector> {
+    client: Client<C>,
+    endpoints: Endpoints,
+    /// requests without a response by then fail with
+    ///  [`BackendError::Timeout`]
+    request_timeout: Option<Duration>,
+}
+
+impl<C> HttpBackend<C> {
+    pub fn new(client: Client<C>, endpoints: Endpoints) -> Self {
+        HttpBackend {
+            client,
+            endpoints,
+            request_timeout: None,
+        }
+    }
+
+    /// Give up on requests that take longer than `timeout`
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+}
+
+impl<C> HttpBackend<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    /// Issue a request, record its latency under `endpoint` and return
+    ///  the body of a successful response
+    async fn request(
+        &self,
+        endpoint: &'static str,
+        req: Result<Request<Body>, hyper::http::Error>,
+    ) -> Result<Bytes, BackendError> {
+        let req = req.map_err(|e| BackendError::transport(endpoint, e))?;
+
+        let response = self.send(endpoint, req);
+        match self.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, response)
+                .await
+                .unwrap_or(Err(BackendError::Timeout { endpoint })),
+            None => response.await,
+        }
+    }
+
+    async fn send(&self, endpoint: &'static str, req: Request<Body>) -> Result<Bytes, BackendError> {
+        let started = Instant::now();
+        let result = self.client.request(req).await;
+        metrics()
+            .http_latency
+            .with_label_values(&[endpoint])
+            .observe(started.elapsed().as_secs_f64());
+
+        let res = result.map_err(|e| BackendError::transport(endpoint, e))?;
+        if res.status() != StatusCode::OK {
+            return Err(BackendError::from_status(endpoint, res.status()));
+        }
+
+        hyper::body::to_bytes(res.into_body())
+            .await
+            .map_err(|e| BackendError::transport(endpoint, e))
+    }
+
+    /// Confirm or deny a flight plan with svc-atc
+    ///
+    /// Both go to the same URL, a denial is labelled as a `decline` so it
+    ///  is timed and broken apart from acknowledgements.
+    async fn answer_order(&self, flight_id: &str, status: AckStatus) -> Result<(), BackendError> {
+        let url = format!("{}/acknowledge", self.endpoints.atc_uri);
+        let endpoint = match status {
+            AckStatus::Confirm => "acknowledge",
+            AckStatus::Deny => "decline",
+        };
+
+        let data = AckRequest {
+            fp_id: flight_id.to_string(),
+            status,
+        };
+
+        let data_str =
+            serde_json::to_string(&data).map_err(|e| BackendError::payload(endpoint, e))?;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header("content-type", "application/json")
+            .body(Body::from(data_str));
+
+        self.request(endpoint, req).await?;
+        Ok(())
+    }
+}
+
+impl<C> TelemetrySink for HttpBackend<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    async fn acquire_token(&self, identifier: &str) -> Result<String, BackendError> {
+        let url = format!("{}/login", self.endpoints.tlm_uri);
+
+        tracing::debug!(%url, "acquiring token");
+        // acquire token
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .header("content-type", "text/plain")
+            .body(Bytes::from(identifier.to_string()).into());
+
+        let body = self.request("login", req).await?;
+        let token = String::from_utf8(body.to_vec())
+            .map_err(|e| BackendError::payload("login", e))?
+            .trim_matches('"')
+            .replace("\"", "");
+
+        tracing::info!("acquired token");
+        Ok(token)
+    }
+
+    async fn send_frame(&self, token: &str, frame: &TelemetryFrame) -> Result<(), BackendError> {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}/netrid", self.endpoints.tlm_uri))
+            .header("content-type", "application/octet-stream")
+            .header("Authorization", format!("Bearer {token}"))
+            .body(Body::from(frame.payload.clone()));
+
+        self.request("netrid", req).await?;
+        Ok(())
+    }
+}
+
+impl<C> OrderSource for HttpBackend<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    async fn get_orders(
+        &self,
+        aircraft_uuid: &str,
+        _identifier: &str,
+    ) -> Result<Vec<FlightPlan>, BackendError> {
+        let url = format!("{}/plans", self.endpoints.atc_uri);
+
+        tracing::debug!(%url, "acquiring plans");
+
+        // acquire plans
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .header("content-type", "application/json")
+            .body(Bytes::from(aircraft_uuid.to_string()).into());
+
+        let body = self.request("plans", req).await?;
+        let plans = serde_json::from_slice::<Vec<FlightPlan>>(&body)
+            .map_err(|e| BackendError::payload("plans", e))?;
+
+        tracing::debug!(plans = plans.len(), "acquired plans");
+        Ok(plans)
+    }
+
+    async fn acknowledge_order(&self, flight_id: &str, _identifier: &str) -> Result<(), BackendError> {
+        tracing::debug!(flight_id, "confirming flight plan");
+        self.answer_order(flight_id, AckStatus::Confirm).await
+    }
+
+    async fn decline_order(&self, flight_id: &str, _identifier: &str) -> Result<(), BackendError> {
+        tracing::debug!(flight_id, "declining flight plan");
+        self.answer_order(flight_id, AckStatus::Deny).await
+    }
+}
+
+impl<C> CargoScanner for HttpBackend<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    async fn parcel_scan(&self, _identifier: &str, scan: CargoScan) -> Result<(), BackendError> {
+        let body = serde_json::to_string(&scan).map_err(|e| BackendError::payload("scan", e))?;
+
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("{}/scan", self.endpoints.cargo_uri))
+            .header("content-type", "application/octet-stream")
+            .body(Body::from(body));
+
+        self.request("scan", req).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server, Version};
+    use std::convert::Infallible;
+    use std::path::PathBuf;
+
+    /// Self-signed CA certificate
+    const CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBkjCCATmgAwIBAgIUTFVBCSrhpX1PHS/DJJgU9EA18UowCgYIKoZIzj0EAwIw
+HjEcMBoGA1UEAwwTc2ltLWNhcnJpZXIgdGVzdCBDQTAgFw0yNjEwMTcwNTIyNDNa
+GA8yMTI2MDkyMzA1MjI0M1owHjEcMBoGA1UEAwwTc2ltLWNhcnJpZXIgdGVzdCBD
+QTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABKhf4eSl41o+O9M8QXmQOa9Jh+jn
+dosRcRgk8iRF+fdwr/6CeoTaXeTRpItbsWza+hHmvnhMMIuWix1U1InmcRyjUzBR
+MB0GA1UdDgQWBBSYWXyvIS1AWC2SzlOeOohEpgDN7jAfBgNVHSMEGDAWgBSYWXyv
+IS1AWC2SzlOeOohEpgDN7jAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0cA
+MEQCIBJp38a9zMnHObF8FVCYhCNTF92NojMTMJSgAILYyDvoAiBuHc/I4NCPTk0C
+nai4HneQ5Fef/Y8Lh6GzleIUq7i3UQ==
+-----END CERTIFICATE-----
+";
+
+    /// A PEM file holding `contents`, removed when dropped
+    struct PemFile(PathBuf);
+
+    impl PemFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let file_name = format!("sim-carrier-{}-{}.pem", name, std::process::id());
+            let path = std::env::temp_dir().join(file_name);
+            std::fs::write(&path, contents).expect("PEM file should be written");
+            PemFile(path)
+        }
+
+        fn options(&self) -> HttpOptions {
+            HttpOptions {
+                ca_bundle: Some(self.0.to_string_lossy().into_owned()),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Drop for PemFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn trusts_the_certificates_of_a_bundle() {
+        let bundle = PemFile::new("ca", CA);
+        let roots = load_ca_bundle(&bundle.options().ca_bundle.unwrap()).expect("bundle loads");
+        assert_eq!(roots.len(), 1);
+        assert!(client(&bundle.options()).is_ok());
+    }
+
+    #[test]
+    fn reports_a_missing_bundle() {
+        let options = HttpOptions {
+            ca_bundle: Some("/nonexistent/sim-carrier-ca.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(client(&options), Err(ClientError::Io(..))));
+    }
+
+    #[test]
+    fn reports_a_bundle_without_certificates() {
+        let bundle = PemFile::new("empty", "not a certificate\n");
+        assert!(matches!(client(&bundle.options()), Err(ClientError::NoCertificates(_))));
+    }
+
+    #[test]
+    fn reports_an_invalid_certificate() {
+        let garbage = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+        let bundle = PemFile::new("invalid", garbage);
+        assert!(matches!(client(&bundle.options()), Err(ClientError::Certificate(..))));
+    }
+
+    #[tokio::test]
+    async fn speaks_http2_with_prior_knowledge() {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                Ok::<_, Infallible>(Response::new(Body::from(format!("{:?}", req.version()))))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into())
+            .http2_only(true)
+            .serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let options = HttpOptions {
+            http2_prior_knowledge: true,
+            ..Default::default()
+        };
+        let client = client(&options).expect("client builds");
+        let response = client
+            .get(format!("http://{addr}/").parse().expect("valid uri"))
+            .await
+            .expect("server answers over HTTP/2");
+        assert_eq!(response.version(), Version::HTTP_2);
+    }
+}
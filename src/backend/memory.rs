@@ -0,0 +1,137 @@
+//! In-process backend that serves flight plans from memory and records
+//!  everything the aircraft sends
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard};
+use svc_atc_client_rest::types::FlightPlan;
+use svc_cargo_client_rest::types::CargoScan;
+
+use super::{CargoScanner, OrderSource, TelemetrySink};
+use crate::error::BackendError;
+use crate::telemetry::TelemetryFrame;
+
+/// A parcel scan received by the [`MemoryBackend`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanRecord {
+    pub identifier: String,
+    pub scanner_id: String,
+    pub cargo_id: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Everything the [`MemoryBackend`] served and received
+#[derive(Debug, Clone, Default)]
+pub struct Records {
+    /// Identifiers that acquired a token, in order
+    pub logins: Vec<String>,
+    /// Telemetry frames, in order of arrival
+    pub frames: Vec<TelemetryFrame>,
+    /// Flight plan ids that were acknowledged, in order
+    pub acknowledged: Vec<String>,
+    /// Flight plan ids that were declined, in order
+    pub declined: Vec<String>,
+    /// Parcel scans, in order of arrival
+    pub scans: Vec<ScanRecord>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    plans: Vec<FlightPlan>,
+    records: Records,
+}
+
+/// Backend keeping flight plans and received messages in memory
+///
+/// Cheap to clone; clones share the same plans and records.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A backend that serves `plans` to every aircraft
+    pub fn with_plans(plans: Vec<FlightPlan>) -> Self {
+        let backend = Self::default();
+        backend.lock().plans = plans;
+        backend
+    }
+
+    /// Serve an additional flight plan
+    pub fn push_plan(&self, plan: FlightPlan) {
+        self.lock().plans.push(plan);
+    }
+
+    /// Copy of everything received so far
+    pub fn records(&self) -> Records {
+        self.lock().records.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // a panic while holding the lock leaves the records intact
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TelemetrySink for MemoryBackend {
+    async fn acquire_token(&self, identifier: &str) -> Result<String, BackendError> {
+        self.lock().records.logins.push(identifier.to_string());
+        Ok(format!("memory-token-{identifier}"))
+    }
+
+    async fn send_frame(&self, _token: &str, frame: &TelemetryFrame) -> Result<(), BackendError> {
+        self.lock().records.frames.push(frame.clone());
+        Ok(())
+    }
+}
+
+impl OrderSource for MemoryBackend {
+    async fn get_orders(
+        &self,
+        _aircraft_uuid: &str,
+        _identifier: &str,
+    ) -> Result<Vec<FlightPlan>, BackendError> {
+        // like svc-atc, only serve plans that were not answered yet
+        let inner = self.lock();
+        let plans = inner
+            .plans
+            .iter()
+            .filter(|p| !inner.records.acknowledged.contains(&p.flight_uuid))
+            .filter(|p| !inner.records.declined.contains(&p.flight_uuid))
+            .cloned()
+            .collect();
+
+        Ok(plans)
+    }
+
+    async fn acknowledge_order(&self, flight_id: &str, _identifier: &str) -> Result<(), BackendError> {
+        self.lock().records.acknowledged.push(flight_id.to_string());
+        Ok(())
+    }
+
+    async fn decline_order(&self, flight_id: &str, _identifier: &str) -> Result<(), BackendError> {
+        self.lock().records.declined.push(flight_id.to_string());
+        Ok(())
+    }
+}
+
+impl CargoScanner for MemoryBackend {
+    async fn parcel_scan(&self, identifier: &str, scan: CargoScan) -> Result<(), BackendError> {
+        self.lock().records.scans.push(ScanRecord {
+            identifier: identifier.to_string(),
+            scanner_id: scan.scanner_id,
+            cargo_id: scan.cargo_id,
+            latitude: scan.latitude,
+            longitude: scan.longitude,
+            timestamp: scan.timestamp,
+        });
+
+        Ok(())
+    }
+}
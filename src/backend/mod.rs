@@ -0,0 +1,112 @@
+//! Interfaces between the simulated aircraft and the services it talks to
+//!
+//! The simulation only depends on the traits in this module. [`http`] talks
+//!  to live svc-telemetry, svc-atc and svc-cargo instances, [`amqp`]
+//!  publishes telemetry to a message broker, [`memory`] keeps everything in
+//!  process for tests and embedding. [`guarded`] puts circuit breakers in
+//!  front of any of them.
+//!
+//! Backends are moved into the link tasks of an aircraft, see
+//!  [`crate::link`], hence the `'static` bound of the traits.
+
+use std::future::Future;
+use svc_atc_client_rest::types::FlightPlan;
+use svc_cargo_client_rest::types::CargoScan;
+
+use crate::error::BackendError;
+use crate::telemetry::TelemetryFrame;
+
+pub mod amqp;
+pub mod guarded;
+pub mod http;
+pub mod memory;
+
+pub use amqp::AmqpTelemetry;
+pub use guarded::Guarded;
+pub use http::HttpBackend;
+pub use memory::MemoryBackend;
+
+/// Receives the NETRID telemetry of an aircraft
+pub trait TelemetrySink: Send + Sync + 'static {
+    /// Acquire a network token for the aircraft `identifier`
+    fn acquire_token(
+        &self,
+        identifier: &str,
+    ) -> impl Future<Output = Result<String, BackendError>> + Send;
+
+    /// Publish a packed NETRID frame
+    fn send_frame(
+        &self,
+        token: &str,
+        frame: &TelemetryFrame,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+}
+
+/// Provides flight plans to an aircraft
+pub trait OrderSource: Send + Sync + 'static {
+    /// All flight plans currently assigned to the aircraft `aircraft_uuid`
+    fn get_orders(
+        &self,
+        aircraft_uuid: &str,
+        identifier: &str,
+    ) -> impl Future<Output = Result<Vec<FlightPlan>, BackendError>> + Send;
+
+    /// Confirm that the aircraft accepted a flight plan
+    fn acknowledge_order(
+        &self,
+        flight_id: &str,
+        identifier: &str,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    /// Tell svc-atc that the aircraft will not fly a flight plan
+    fn decline_order(
+        &self,
+        flight_id: &str,
+        identifier: &str,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+}
+
+/// Records parcels entering and leaving an aircraft
+pub trait CargoScanner: Send + Sync + 'static {
+    /// Report a parcel scan by the aircraft `identifier`
+    fn parcel_scan(
+        &self,
+        identifier: &str,
+        scan: CargoScan,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+}
+
+/// Telemetry sink selected at runtime
+#[derive(Clone)]
+pub enum TelemetryOutput {
+    Http(HttpBackend),
+    Amqp(AmqpTelemetry),
+    /// log in and post to svc-telemetry, and also publish every frame
+    ///  to the broker
+    Both(HttpBackend, AmqpTelemetry),
+}
+
+impl TelemetrySink for TelemetryOutput {
+    async fn acquire_token(&self, identifier: &str) -> Result<String, BackendError> {
+        match self {
+            TelemetryOutput::Http(http) => http.acquire_token(identifier).await,
+            TelemetryOutput::Amqp(amqp) => amqp.acquire_token(identifier).await,
+            TelemetryOutput::Both(http, _) => http.acquire_token(identifier).await,
+        }
+    }
+
+    async fn send_frame(&self, token: &str, frame: &TelemetryFrame) -> Result<(), BackendError> {
+        match self {
+            TelemetryOutput::Http(http) => http.send_frame(token, frame).await,
+            TelemetryOutput::Amqp(amqp) => amqp.send_frame(token, frame).await,
+            TelemetryOutput::Both(http, amqp) => {
+                // the broker is a side channel, only svc-telemetry decides
+                //  if the token is still valid
+                if let Err(e) = amqp.send_frame(token, frame).await {
+                    tracing::warn!(error = %e, kind = %frame.kind, "could not publish update");
+                }
+                http.send_frame(token, frame).await
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use chrono::{DateTime, Utc};
+use svc_atc_client_rest::types::*;
+
+use crate::battery::{self, Battery, BatteryOptions};
+use crate::clock::SimClock;
+use crate::kinematics::KinematicLimits;
+use crate::orders::{init_plan, plan_distance};
+use crate::phase::{self, PhaseOptions};
+use crate::profile::{self, Infeasible, PerformanceProfile};
+use crate::telemetry::update_location;
+use crate::wind::WindModel;
+use crate::State;
+
+/// Give up on a plan after this many times its planned duration
+const MAX_DURATION_FACTOR: u64 = 4;
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    /// the clock never advances with a step of 0 ms
+    ZeroStep,
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::Io(e) => write!(f, "could not read flight plans: {}", e),
+            BatchError::Parse(e) => write!(f, "could not parse flight plans: {}", e),
+            BatchError::ZeroStep => write!(f, "time step must be greater than zero"),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BatchError::Io(e) => Some(e),
+            BatchError::Parse(e) => Some(e),
+            BatchError::ZeroStep => None,
+        }
+    }
+}
+
+/// Outcome of fast-forwarding a single flight plan
+pub enum Verdict {
+    /// Reached the target within its timeslot
+    Feasible,
+    /// Reached the target after the end of its timeslot
+    Late,
+    /// The plan has no path or no time to fly it
+    Invalid(&'static str),
+    /// Did not reach the target within the step limit
+    Incomplete,
+    /// Landed short of the target after breaching the battery reserve
+    Diverted,
+    /// The battery does not cover the plan while keeping the reserve
+    Uncharged,
+    /// Beyond the performance profile of the aircraft
+    Infeasible(Infeasible),
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Feasible => write!(f, "feasible"),
+            Verdict::Late => write!(f, "late"),
+            Verdict::Invalid(reason) => write!(f, "invalid ({reason})"),
+            Verdict::Incomplete => write!(f, "incomplete"),
+            Verdict::Diverted => write!(f, "diverted"),
+            Verdict::Uncharged => write!(f, "not enough charge"),
+            Verdict::Infeasible(reason) => write!(f, "infeasible ({reason})"),
+        }
+    }
+}
+
+/// Result of fast-forwarding a single flight plan
+pub struct PlanReport {
+    pub session_id: String,
+    pub verdict: Verdict,
+    pub distance_m: f64,
+    pub ground_velocity_m_s: f64,
+    pub arrival: Option<DateTime<Utc>>,
+    /// drawn from the battery, when simulated
+    pub energy_wh: Option<f64>,
+}
+
+/// Load flight plans from a JSON file
+pub fn load(path: &str) -> Result<Vec<FlightPlan>, BatchError> {
+    let contents = std::fs::read_to_string(path).map_err(BatchError::Io)?;
+    serde_json::from_str(&contents).map_err(BatchError::Parse)
+}
+
+/// Fly a flight plan with a stepped clock and no backend
+///
+/// The aircraft starts at rest at the first point of the path at the end of
+///  the origin timeslot, the same moment the live simulation activates a
+///  plan, flies its phases according to `phases` within `limits` and
+///  `profile` and arrives on touchdown. With `battery` it starts fully
+///  charged. Gusts of `wind` are seeded by the session id.
+pub fn simulate(
+    plan: FlightPlan,
+    dt_ms: u64,
+    limits: &KinematicLimits,
+    phases: &PhaseOptions,
+    profile: &PerformanceProfile,
+    battery: Option<&BatteryOptions>,
+    wind: &WindModel,
+) -> PlanReport {
+    let mut report = PlanReport {
+        session_id: plan.session_id.clone(),
+        verdict: Verdict::Incomplete,
+        distance_m: plan_distance(&plan),
+        ground_velocity_m_s: 0.0,
+        arrival: None,
+        energy_wh: None,
+    };
+
+    if dt_ms == 0 {
+        report.verdict = Verdict::Invalid("time step of 0 ms");
+        return report;
+    }
+
+    let Some(origin) = plan.path.first().cloned() else {
+        report.verdict = Verdict::Invalid("empty path");
+        return report;
+    };
+
+    if plan.target_timeslot_start <= plan.origin_timeslot_end {
+        report.verdict = Verdict::Invalid("target timeslot starts before departure");
+        return report;
+    }
+
+    let clock = SimClock::stepped(plan.origin_timeslot_end, dt_ms);
+    let departure_ms = clock.now_ms();
+    let planned_ms = (plan.target_timeslot_start.timestamp_millis() as u64) - departure_ms;
+    let deadline_ms = departure_ms + planned_ms * MAX_DURATION_FACTOR;
+    let target_timeslot_end = plan.target_timeslot_end;
+
+    let mut state = State::new(plan.session_id.clone(), String::new(), origin);
+    let mut wind = wind.for_aircraft(&plan.session_id);
+    if let Err(e) = profile::check(&plan, &state, departure_ms, profile, phases) {
+        report.verdict = Verdict::Infeasible(e);
+        return report;
+    }
+
+    if let Some(options) = battery {
+        state.battery = Some(Battery::full(options.capacity_wh));
+        if !battery::can_fly(&plan, &state, departure_ms, options, phases, profile) {
+            report.verdict = Verdict::Uncharged;
+            return report;
+        }
+    }
+
+    // picks up the parcels, there is no backend to report the scans to
+    let _ = init_plan(&clock, &mut state, departure_ms, plan, profile);
+    report.ground_velocity_m_s = state.target_ground_velocity_m_s;
+
+    let limits = profile.limits(limits);
+    let mut last_tick = departure_ms;
+    while clock.now_ms() < deadline_ms {
+        clock.advance();
+        let current_tick = clock.now_ms();
+        phase::advance(current_tick, &mut state, phases, profile);
+        update_location(&current_tick, &last_tick, &mut state, &limits, phases, &mut wind);
+        if let Some(options) = battery {
+            battery::update(current_tick, last_tick, &mut state, options, phases);
+        }
+        last_tick = current_tick;
+
+        let arrived = state
+            .current_plan
+            .as_ref()
+            .map(|p| p.path.is_empty())
+            .unwrap_or(true);
+
+        if arrived {
+            let arrival = clock.now();
+            report.arrival = Some(arrival);
+            report.verdict = if state.diverted {
+                Verdict::Diverted
+            } else if arrival <= target_timeslot_end {
+                Verdict::Feasible
+            } else {
+                Verdict::Late
+            };
+
+            break;
+        }
+    }
+
+    report.energy_wh = state.battery.map(|b| b.capacity_wh - b.charge_wh);
+    report
+}
+
+/// Fast-forward every flight plan in a file and print a report
+///
+/// Returns the number of plans that are not feasible.
+pub fn run(
+    path: &str,
+    dt_ms: u64,
+    limits: &KinematicLimits,
+    phases: &PhaseOptions,
+    profile: &PerformanceProfile,
+    battery: Option<&BatteryOptions>,
+    wind: &WindModel,
+) -> Result<usize, BatchError> {
+    if dt_ms == 0 {
+        return Err(BatchError::ZeroStep);
+    }
+
+    let plans = load(path)?;
+    let started = std::time::Instant::now();
+    tracing::info!(plans = plans.len(), dt_ms, "fast-forwarding flight plans");
+
+    let total = plans.len();
+    let mut failed = 0;
+    for plan in plans {
+        let report = simulate(plan, dt_ms, limits, phases, profile, battery, wind);
+        if !matches!(report.verdict, Verdict::Feasible) {
+            failed += 1;
+        }
+
+        tracing::info!(
+            session = %report.session_id,
+            verdict = %report.verdict,
+            distance_m = report.distance_m,
+            ground_velocity_m_s = report.ground_velocity_m_s,
+            arrival = report.arrival.map(|a| a.to_rfc3339()).as_deref().unwrap_or("-"),
+            energy_wh = report.energy_wh,
+            "plan report"
+        );
+    }
+
+    tracing::info!(
+        feasible = total - failed,
+        total,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "batch complete"
+    );
+
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::flight_plan;
+
+    fn fly(plan: FlightPlan, battery: &BatteryOptions) -> PlanReport {
+        simulate(
+            plan,
+            50,
+            &KinematicLimits::default(),
+            &PhaseOptions::default(),
+            &PerformanceProfile::default(),
+            Some(battery),
+            &WindModel::default(),
+        )
+    }
+
+    #[test]
+    fn draws_more_energy_with_the_parcels_on_board() {
+        let battery = BatteryOptions::default();
+        let loaded = fly(flight_plan(Utc::now()), &battery);
+
+        let mut plan = flight_plan(Utc::now());
+        plan.acquire.clear();
+        plan.deliver.clear();
+        let empty = fly(plan, &battery);
+
+        assert!(matches!(loaded.verdict, Verdict::Feasible));
+        assert!(matches!(empty.verdict, Verdict::Feasible));
+        assert!(loaded.energy_wh.unwrap() > empty.energy_wh.unwrap());
+    }
+
+    #[test]
+    fn reports_plans_the_battery_does_not_cover() {
+        let battery = BatteryOptions {
+            capacity_wh: 50.0,
+            ..Default::default()
+        };
+
+        let report = fly(flight_plan(Utc::now()), &battery);
+        assert!(matches!(report.verdict, Verdict::Uncharged));
+        assert_eq!(report.verdict.to_string(), "not enough charge");
+    }
+
+    #[test]
+    fn refuses_a_zero_time_step() {
+        let result = run(
+            "plans.json",
+            0,
+            &KinematicLimits::default(),
+            &PhaseOptions::default(),
+            &PerformanceProfile::default(),
+            None,
+            &WindModel::default(),
+        );
+        assert!(matches!(result, Err(BatchError::ZeroStep)));
+
+        let report = simulate(
+            flight_plan(Utc::now()),
+            0,
+            &KinematicLimits::default(),
+            &PhaseOptions::default(),
+            &PerformanceProfile::default(),
+            None,
+            &WindModel::default(),
+        );
+        assert!(matches!(report.verdict, Verdict::Invalid(_)));
+    }
+}
@@ -0,0 +1,412 @@
+//! Battery state of charge and energy use
+//!
+//! The battery drains on every tick with the power drawn in the current
+//!  flight phase: avionics before takeoff, and in the air the power to carry
+//!  the aircraft and its payload, to overcome drag at speed and to climb.
+//!  It charges in [`Activity::Charging`]. Plans the charge does not cover,
+//!  keeping the reserve, are declined; an aircraft that is about to need
+//!  the reserve to get down and cannot reach its target any more lands in
+//!  place.
+
+use geo::point;
+use geo::prelude::*;
+use serde::{Deserialize, Serialize};
+use svc_atc_client_rest::types::*;
+
+use crate::orders::parcels_kg;
+use crate::phase::PhaseOptions;
+use crate::profile::PerformanceProfile;
+use crate::{Activity, State};
+
+/// Gravitational acceleration in m/s²
+const G: f64 = 9.81;
+
+/// Battery and power draw of the aircraft
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct BatteryOptions {
+    pub capacity_wh: f64,
+    /// mass without payload
+    pub empty_mass_kg: f64,
+    /// power to hover, per kg of aircraft and payload
+    pub hover_power_w_per_kg: f64,
+    /// power to overcome drag, in W per (m/s)³ of airspeed
+    pub drag_power_w_s3_m3: f64,
+    /// share of the power drawn for climbing that becomes height
+    pub climb_efficiency: f64,
+    /// avionics, drawn during the preflight checks and while charging
+    pub ground_power_w: f64,
+    pub charge_power_w: f64,
+    /// state of charge in % below which a warning is logged
+    pub warning_pct: f64,
+    /// state of charge in % kept for contingencies; plans may not use it
+    pub reserve_pct: f64,
+}
+
+impl Default for BatteryOptions {
+    fn default() -> Self {
+        BatteryOptions {
+            capacity_wh: 1000.0,
+            empty_mass_kg: 15.0,
+            hover_power_w_per_kg: 100.0,
+            drag_power_w_s3_m3: 0.4,
+            climb_efficiency: 0.7,
+            ground_power_w: 20.0,
+            charge_power_w: 1000.0,
+            warning_pct: 30.0,
+            reserve_pct: 15.0,
+        }
+    }
+}
+
+/// State of charge relative to the thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChargeLevel {
+    Normal,
+    /// below `warning_pct`
+    Low,
+    /// below `reserve_pct`
+    Reserve,
+    Depleted,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Battery {
+    pub capacity_wh: f64,
+    pub charge_wh: f64,
+    pub level: ChargeLevel,
+}
+
+impl Battery {
+    /// A fully charged battery of `capacity_wh`
+    pub fn full(capacity_wh: f64) -> Self {
+        Battery {
+            capacity_wh,
+            charge_wh: capacity_wh,
+            level: ChargeLevel::Normal,
+        }
+    }
+
+    /// State of charge in %
+    pub fn percent(&self) -> f64 {
+        if self.capacity_wh <= 0.0 {
+            return 0.0;
+        }
+
+        100.0 * self.charge_wh / self.capacity_wh
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.charge_wh >= self.capacity_wh
+    }
+
+    fn reserve_wh(&self, options: &BatteryOptions) -> f64 {
+        self.capacity_wh * options.reserve_pct / 100.0
+    }
+
+    fn level(&self, options: &BatteryOptions) -> ChargeLevel {
+        let percent = self.percent();
+        if self.charge_wh <= 0.0 {
+            ChargeLevel::Depleted
+        } else if percent <= options.reserve_pct {
+            ChargeLevel::Reserve
+        } else if percent <= options.warning_pct {
+            ChargeLevel::Low
+        } else {
+            ChargeLevel::Normal
+        }
+    }
+}
+
+/// Drain or charge the battery for the time since `last_ms`
+///
+/// Logs when the charge drops below a threshold. Once the charge above the
+///  reserve no longer covers the way down from the current altitude, the
+///  aircraft lands in place if what is left does not reach the target.
+pub fn update(
+    current_ms: u64,
+    last_ms: u64,
+    state: &mut State,
+    options: &BatteryOptions,
+    phases: &PhaseOptions,
+) {
+    let elapsed_s = (current_ms.saturating_sub(last_ms) as f64) / 1000.0;
+    let power_w = power_w(state, options);
+    let descent_wh = descent_wh(state, options, phases);
+    let Some(ref mut battery) = state.battery else {
+        return;
+    };
+
+    battery.charge_wh =
+        (battery.charge_wh - power_w * elapsed_s / 3600.0).clamp(0.0, battery.capacity_wh);
+    let short = battery.charge_wh <= battery.reserve_wh(options) + descent_wh;
+
+    let level = battery.level(options);
+    let previous = std::mem::replace(&mut battery.level, level);
+    if level > previous {
+        let percent = battery.percent();
+        match level {
+            ChargeLevel::Normal => {}
+            ChargeLevel::Low => tracing::warn!(current_ms, percent, "battery low"),
+            ChargeLevel::Reserve => {
+                tracing::warn!(current_ms, percent, "battery reserve breached")
+            }
+            ChargeLevel::Depleted => tracing::error!(current_ms, "battery depleted"),
+        }
+    }
+
+    if short {
+        divert_if_short(current_ms, state, options, phases);
+    }
+}
+
+/// If the charge covers `plan` when started at `now_ms`, keeping the
+///  reserve
+///
+/// The plan is flown from the current position at the speed `profile`
+///  plans for it, after the time on the pad unless the aircraft is in the
+///  air already. Without a battery every plan is covered.
+pub fn can_fly(
+    plan: &FlightPlan,
+    state: &State,
+    now_ms: u64,
+    options: &BatteryOptions,
+    phases: &PhaseOptions,
+    profile: &PerformanceProfile,
+) -> bool {
+    let Some(ref battery) = state.battery else {
+        return true;
+    };
+
+    let mass_kg = options.empty_mass_kg + state.payload_kg + parcels_kg(&plan.acquire);
+
+    let (preflight_s, takeoff_s) = if state.activity.is_airborne() {
+        (0.0, 0.0)
+    } else {
+        (
+            phases.preflight_ms as f64 / 1000.0,
+            phases.spool_up_ms as f64 / 1000.0
+                + climb_time_s(phases.takeoff_height_m, phases.takeoff_climb_rate_m_s),
+        )
+    };
+    let departure_ms = now_ms + ((preflight_s + takeoff_s) * 1000.0) as u64;
+    let speed_m_s = profile.ground_velocity(plan, &state.position, departure_ms);
+
+    let ground_j = options.ground_power_w * preflight_s
+        + flight_power_w(mass_kg, 0.0, 0.0, options) * takeoff_s;
+    let required_wh = ground_j / 3600.0
+        + flight_wh(&state.position, &plan.path, mass_kg, speed_m_s, options, phases);
+    let available_wh = battery.charge_wh - battery.reserve_wh(options);
+    if required_wh <= available_wh {
+        return true;
+    }
+
+    tracing::warn!(
+        session = %plan.session_id,
+        required_wh,
+        available_wh,
+        "not enough charge for flight plan"
+    );
+    false
+}
+
+/// Power drawn in the current phase, negative while charging
+fn power_w(state: &State, options: &BatteryOptions) -> f64 {
+    let mass_kg = options.empty_mass_kg + state.payload_kg;
+
+    match state.activity {
+        // powered down
+        Activity::Idle | Activity::OutOfService => 0.0,
+        Activity::Preflight => options.ground_power_w,
+        Activity::Charging => options.ground_power_w - options.charge_power_w,
+        // the rotors turn at hover speed without lifting off yet
+        Activity::SpoolUp => flight_power_w(mass_kg, 0.0, 0.0, options),
+        _ => flight_power_w(mass_kg, state.airspeed_m_s, state.vertical_velocity_m_s, options),
+    }
+}
+
+/// Power to fly `mass_kg` at `speed_m_s`, climbing at `vertical_speed_m_s`
+fn flight_power_w(mass_kg: f64, speed_m_s: f64, vertical_speed_m_s: f64, options: &BatteryOptions) -> f64 {
+    let hover_w = mass_kg * options.hover_power_w_per_kg;
+    let drag_w = options.drag_power_w_s3_m3 * speed_m_s.abs().powi(3);
+    let climb_w = mass_kg * G * vertical_speed_m_s.max(0.0) / options.climb_efficiency.max(0.01);
+    hover_w + drag_w + climb_w
+}
+
+/// Energy in Wh to fly from `from` through `path` at `speed_m_s`, then
+///  approach the last point and land on it
+fn flight_wh(
+    from: &PointZ,
+    path: &[PointZ],
+    mass_kg: f64,
+    speed_m_s: f64,
+    options: &BatteryOptions,
+    phases: &PhaseOptions,
+) -> f64 {
+    let mut distance_m = 0.0;
+    let mut climb_m = 0.0;
+    let mut last = (from.longitude, from.latitude, from.altitude_meters);
+    for (i, p) in path.iter().enumerate() {
+        // the last leg is flown at the approach height
+        let altitude = if i + 1 == path.len() {
+            p.altitude_meters + phases.takeoff_height_m
+        } else {
+            p.altitude_meters
+        };
+
+        let p1 = point!(x: last.0, y: last.1);
+        let p2 = point!(x: p.longitude, y: p.latitude);
+        distance_m += p1.haversine_distance(&p2);
+        climb_m += (altitude - last.2).max(0.0);
+        last = (p.longitude, p.latitude, altitude);
+    }
+
+    let cruise_s = if speed_m_s > 0.0 { distance_m / speed_m_s } else { 0.0 };
+    let landing_s = climb_time_s(phases.takeoff_height_m, phases.landing_descent_rate_m_s);
+    let joules = flight_power_w(mass_kg, speed_m_s, 0.0, options) * cruise_s
+        + flight_power_w(mass_kg, 0.0, 0.0, options) * landing_s
+        + mass_kg * G * climb_m / options.climb_efficiency.max(0.01);
+
+    joules / 3600.0
+}
+
+/// Energy in Wh to descend from the current altitude at the landing rate
+fn descent_wh(state: &State, options: &BatteryOptions, phases: &PhaseOptions) -> f64 {
+    let mass_kg = options.empty_mass_kg + state.payload_kg;
+    let height_m = state.position.altitude_meters.max(0.0);
+    let descent_s = climb_time_s(height_m, phases.landing_descent_rate_m_s);
+    flight_power_w(mass_kg, 0.0, 0.0, options) * descent_s / 3600.0
+}
+
+fn climb_time_s(height_m: f64, rate_m_s: f64) -> f64 {
+    if rate_m_s > 0.0 {
+        height_m / rate_m_s
+    } else {
+        0.0
+    }
+}
+
+/// Land in place, unless the charge left still reaches the target
+///
+/// The parcels stay on board; the plan ends on touchdown without
+///  delivering them.
+fn divert_if_short(current_ms: u64, state: &mut State, options: &BatteryOptions, phases: &PhaseOptions) {
+    if state.diverted || !state.activity.is_airborne() || state.activity == Activity::Landing {
+        return;
+    }
+
+    let charge_wh = state.battery.map(|b| b.charge_wh).unwrap_or_default();
+    let mass_kg = options.empty_mass_kg + state.payload_kg;
+    let Some(ref mut plan) = state.current_plan else {
+        // checked on every tick, the breach itself is logged once
+        tracing::debug!(current_ms, "holding with the battery reserve breached");
+        return;
+    };
+
+    let speed_m_s = state.target_ground_velocity_m_s.max(phases.approach_speed_m_s);
+    let required_wh = flight_wh(&state.position, &plan.path, mass_kg, speed_m_s, options, phases);
+    if required_wh <= charge_wh {
+        tracing::debug!(current_ms, required_wh, charge_wh, "enough charge left to reach the target");
+        return;
+    }
+
+    tracing::warn!(
+        current_ms,
+        required_wh,
+        charge_wh,
+        session = %plan.session_id,
+        "not enough charge to reach the target, landing in place"
+    );
+    plan.path = vec![PointZ {
+        latitude: state.position.latitude,
+        longitude: state.position.longitude,
+        altitude_meters: 0.0,
+    }];
+    state.diverted = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    use crate::testing::{flight_plan, state};
+
+    fn with_battery(capacity_wh: f64) -> State {
+        State {
+            battery: Some(Battery::full(capacity_wh)),
+            ..state()
+        }
+    }
+
+    #[test]
+    fn covers_plans_due_far_ahead_at_the_cruise_speed() {
+        let start = Utc::now();
+        let mut plan = flight_plan(start);
+        plan.target_timeslot_start = start + Duration::hours(2);
+        plan.target_timeslot_end = start + Duration::hours(2) + Duration::seconds(30);
+
+        let now_ms = start.timestamp_millis() as u64;
+        let options = BatteryOptions::default();
+        let phases = PhaseOptions::default();
+        let profile = PerformanceProfile::default();
+        assert!(can_fly(&plan, &with_battery(1000.0), now_ms, &options, &phases, &profile));
+    }
+
+    #[test]
+    fn declines_plans_that_need_the_reserve() {
+        let start = Utc::now();
+        let plan = flight_plan(start);
+        let now_ms = start.timestamp_millis() as u64;
+        let options = BatteryOptions::default();
+        let phases = PhaseOptions::default();
+        let profile = PerformanceProfile::default();
+        assert!(can_fly(&plan, &with_battery(1000.0), now_ms, &options, &phases, &profile));
+        assert!(!can_fly(&plan, &with_battery(50.0), now_ms, &options, &phases, &profile));
+        assert!(can_fly(&plan, &state(), now_ms, &options, &phases, &profile));
+    }
+
+    #[test]
+    fn drains_in_the_air_and_charges_on_the_ground() {
+        let options = BatteryOptions::default();
+        let phases = PhaseOptions::default();
+        let mut state = with_battery(1000.0);
+        state.activity = Activity::Cruise;
+        state.airspeed_m_s = 10.0;
+
+        // a minute at hover power and the drag of 10 m/s
+        update(60_000, 0, &mut state, &options, &phases);
+        let charge_wh = state.battery.expect("battery").charge_wh;
+        let drawn_wh = (15.0 * 100.0 + 0.4 * 1000.0) / 60.0;
+        assert!((1000.0 - charge_wh - drawn_wh).abs() < 1e-9, "{charge_wh} Wh left");
+
+        // powered down on the pad
+        state.activity = Activity::Idle;
+        update(120_000, 60_000, &mut state, &options, &phases);
+        assert_eq!(state.battery.expect("battery").charge_wh, charge_wh);
+
+        state.activity = Activity::Charging;
+        update(300_000, 120_000, &mut state, &options, &phases);
+        assert!(state.battery.expect("battery").is_full());
+    }
+
+    #[test]
+    fn reports_each_charge_level() {
+        let options = BatteryOptions::default();
+        let phases = PhaseOptions::default();
+        let mut state = with_battery(1000.0);
+        state.activity = Activity::Hold;
+
+        let mut levels = vec![];
+        for minute in 1..=60 {
+            update(minute * 60_000, (minute - 1) * 60_000, &mut state, &options, &phases);
+            let level = state.battery.expect("battery").level;
+            if levels.last() != Some(&level) {
+                levels.push(level);
+            }
+        }
+
+        use ChargeLevel::*;
+        assert_eq!(levels, vec![Normal, Low, Reserve, Depleted]);
+    }
+}
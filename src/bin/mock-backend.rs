@@ -0,0 +1,97 @@
+use clap::Parser;
+use std::net::SocketAddr;
+
+use sim_carrier::logging::{self, LogFormat};
+use sim_carrier::{batch, mock::MockServer};
+
+/// Local stand-in for svc-telemetry, svc-atc and svc-cargo
+///
+/// Point the telemetry, atc and cargo ports of the simulator at the port of
+///  this server.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// address to listen on
+    #[arg(long, env = "MOCK_HOST", default_value = "0.0.0.0")]
+    host: String,
+
+    /// port to listen on
+    #[arg(long, env = "MOCK_PORT", default_value_t = 8000)]
+    port: u16,
+
+    /// flight plans file (JSON) served from /atc/plans
+    #[arg(long, env = "MOCK_PLANS")]
+    plans: Option<String>,
+
+    /// write everything recorded to this file (JSON) on shutdown
+    #[arg(long, env = "MOCK_RECORD")]
+    record: Option<String>,
+
+    /// log line format
+    #[arg(long, value_enum, env = "MOCK_LOG_FORMAT", default_value = "text")]
+    log_format: LogFormat,
+
+    /// log filter, e.g. `info` or `sim_carrier=debug,hyper=warn`
+    #[arg(long, env = "RUST_LOG", default_value = "info")]
+    log_level: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    logging::init(args.log_format, &args.log_level);
+
+    let plans = match args.plans {
+        Some(ref path) => match batch::load(path) {
+            Ok(plans) => plans,
+            Err(e) => {
+                tracing::error!(%path, error = %e, "could not load flight plans");
+                std::process::exit(1);
+            }
+        },
+        None => vec![],
+    };
+
+    let addr: SocketAddr = match format!("{}:{}", args.host, args.port).parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            tracing::error!(error = %e, "invalid address");
+            std::process::exit(1);
+        }
+    };
+
+    let mock = MockServer::new(plans);
+    let (addr, server) = match mock.bind(&addr) {
+        Ok(bound) => bound,
+        Err(e) => {
+            tracing::error!(%addr, error = %e, "could not bind");
+            std::process::exit(1);
+        }
+    };
+
+    tracing::info!(%addr, "mock backend listening");
+    tokio::select! {
+        result = server => {
+            if let Err(e) = result {
+                tracing::error!(error = %e, "server error");
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            tracing::info!("shutting down");
+        }
+    }
+
+    let Some(path) = args.record else {
+        return;
+    };
+
+    let records = mock.records();
+    let result = serde_json::to_string_pretty(&records)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(_) => tracing::info!(%path, "wrote records"),
+        Err(e) => tracing::error!(%path, error = %e, "could not write records"),
+    }
+}
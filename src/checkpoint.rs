@@ -0,0 +1,123 @@
+//! Periodic checkpoints of the fleet, to resume after a restart
+//!
+//! The fleet status board is written to a local JSON file at a fixed
+//!  interval, and once more after shutdown. On `--resume` each aircraft
+//!  picks up its position, activity, current plan with the remaining path,
+//!  queued plans and the parcels on board from the file.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::fleet::{AircraftStatus, StatusBoard};
+
+/// Where and how often checkpoints are written
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CheckpointOptions {
+    /// checkpoints are only written when a path is set
+    pub path: Option<String>,
+    /// wall clock time between checkpoints
+    pub interval_ms: u64,
+    /// restore the aircraft from `path` on startup
+    pub resume: bool,
+}
+
+impl Default for CheckpointOptions {
+    fn default() -> Self {
+        CheckpointOptions {
+            path: None,
+            interval_ms: 10_000,
+            resume: false,
+        }
+    }
+}
+
+/// Contents of a checkpoint file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// wall clock time the checkpoint was written
+    pub saved_at: DateTime<Utc>,
+    /// state of each aircraft, keyed by aircraft name
+    pub aircraft: HashMap<String, AircraftStatus>,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(String, std::io::Error),
+    Parse(String, serde_json::Error),
+    Encode(serde_json::Error),
+    Poisoned,
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(path, e) => write!(f, "could not access checkpoint {}: {}", path, e),
+            CheckpointError::Parse(path, e) => {
+                write!(f, "could not parse checkpoint {}: {}", path, e)
+            }
+            CheckpointError::Encode(e) => write!(f, "could not encode checkpoint: {}", e),
+            CheckpointError::Poisoned => write!(f, "fleet status board is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// Load the checkpoint at `path`
+pub fn load(path: &str) -> Result<Checkpoint, CheckpointError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| CheckpointError::Io(path.to_string(), e))?;
+    serde_json::from_str(&contents).map_err(|e| CheckpointError::Parse(path.to_string(), e))
+}
+
+/// Load the checkpoint at `path` to resume from, if one was written
+///
+/// A missing file is not an error: the first start of a fleet has nothing
+///  to resume.
+pub fn load_for_resume(path: &str) -> Result<Option<Checkpoint>, CheckpointError> {
+    match load(path) {
+        Ok(checkpoint) => Ok(Some(checkpoint)),
+        Err(CheckpointError::Io(_, e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write the status board to `path`
+///
+/// The checkpoint is written next to `path` first and then moved in place,
+///  so a crash while writing leaves the previous checkpoint intact.
+pub fn save(path: &str, status: &StatusBoard) -> Result<(), CheckpointError> {
+    let checkpoint = {
+        let board = status.lock().map_err(|_| CheckpointError::Poisoned)?;
+        Checkpoint {
+            saved_at: Utc::now(),
+            aircraft: board.clone(),
+        }
+    };
+
+    let json = serde_json::to_string_pretty(&checkpoint).map_err(CheckpointError::Encode)?;
+    let partial = format!("{}.partial", path);
+    std::fs::write(&partial, json).map_err(|e| CheckpointError::Io(partial.clone(), e))?;
+    std::fs::rename(&partial, path).map_err(|e| CheckpointError::Io(path.to_string(), e))
+}
+
+/// Write a checkpoint every `interval_ms`
+pub async fn run(path: String, interval_ms: u64, status: StatusBoard) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(interval_ms));
+
+    loop {
+        interval.tick().await;
+
+        // nothing to save before the first tick of the aircraft
+        if status.lock().is_ok_and(|board| board.is_empty()) {
+            continue;
+        }
+
+        match save(&path, &status) {
+            Ok(()) => tracing::debug!(%path, "checkpoint written"),
+            Err(e) => tracing::warn!(error = %e, "could not write checkpoint"),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// Simulation clock
+///
+/// A real time clock starts at the wall clock time of its creation and
+///  then runs `speed` times faster than the wall clock. A stepped clock
+///  only moves when advanced, by a fixed step, so the simulation can run
+///  as fast as the CPU allows.
+///
+/// Clones share the time of a stepped clock, so a fleet runs on a single
+///  clock.
+#[derive(Debug, Clone)]
+pub enum SimClock {
+    RealTime {
+        origin_wall: Instant,
+        origin_sim: DateTime<Utc>,
+        speed: f64,
+    },
+    Stepped(Arc<Steps>),
+}
+
+/// Time of a stepped clock, shared by every clone
+///
+/// The aircraft that [`SimClock::join`] the clock take the steps together:
+///  each waits in [`SimClock::next_step`] until all of them finished the
+///  current one.
+#[derive(Debug)]
+pub struct Steps {
+    dt_ms: u64,
+    state: Mutex<StepState>,
+    /// steps taken so far, watched by the participants waiting for the next
+    taken: watch::Sender<u64>,
+}
+
+#[derive(Debug)]
+struct StepState {
+    now: DateTime<Utc>,
+    participants: usize,
+    /// participants waiting for the next step
+    waiting: usize,
+}
+
+impl Steps {
+    fn lock(&self) -> MutexGuard<'_, StepState> {
+        // the state is consistent after every statement, a panic cannot
+        //  leave it half updated
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a step, with the lock held
+    fn step(&self, state: &mut StepState) {
+        state.now += Duration::milliseconds(self.dt_ms as i64);
+        state.waiting = 0;
+        self.taken.send_modify(|taken| *taken += 1);
+    }
+}
+
+/// Membership of a participant of a stepped clock, it leaves when dropped
+#[derive(Debug)]
+pub struct Participant {
+    steps: Option<Arc<Steps>>,
+}
+
+impl Drop for Participant {
+    fn drop(&mut self) {
+        let Some(ref steps) = self.steps else {
+            return;
+        };
+
+        let mut state = steps.lock();
+        state.participants = state.participants.saturating_sub(1);
+        // the others were only waiting for this one
+        if state.waiting > 0 && state.waiting >= state.participants {
+            steps.step(&mut state);
+        }
+    }
+}
+
+impl SimClock {
+    /// Create a clock starting now and running at `speed` times real time
+    pub fn new(speed: f64) -> Self {
+        SimClock::RealTime {
+            origin_wall: Instant::now(),
+            origin_sim: Utc::now(),
+            speed,
+        }
+    }
+
+    /// Create a clock starting at `start` that moves `dt_ms` per step
+    pub fn stepped(start: DateTime<Utc>, dt_ms: u64) -> Self {
+        let state = StepState {
+            now: start,
+            participants: 0,
+            waiting: 0,
+        };
+
+        SimClock::Stepped(Arc::new(Steps {
+            dt_ms,
+            state: Mutex::new(state),
+            taken: watch::channel(0).0,
+        }))
+    }
+
+    /// If the clock is only moved by [`SimClock::advance`]
+    pub fn is_stepped(&self) -> bool {
+        matches!(self, SimClock::Stepped(_))
+    }
+
+    /// Move a stepped clock forward by one step, no-op for a real time clock
+    ///
+    /// The step is taken right away, for every clone of the clock.
+    pub fn advance(&self) {
+        if let SimClock::Stepped(steps) = self {
+            steps.step(&mut steps.lock());
+        }
+    }
+
+    /// Take the steps of a stepped clock together with the other
+    ///  participants, until the returned membership is dropped
+    pub fn join(&self) -> Participant {
+        let steps = match self {
+            SimClock::Stepped(steps) => steps,
+            SimClock::RealTime { .. } => return Participant { steps: None },
+        };
+
+        steps.lock().participants += 1;
+        Participant {
+            steps: Some(steps.clone()),
+        }
+    }
+
+    /// Wait until every participant finished the current step, then move a
+    ///  stepped clock forward by one step; no-op for a real time clock
+    pub async fn next_step(&self) {
+        let SimClock::Stepped(steps) = self else {
+            return;
+        };
+
+        let mut taken = steps.taken.subscribe();
+        {
+            let mut state = steps.lock();
+            state.waiting += 1;
+            if state.waiting >= state.participants {
+                steps.step(&mut state);
+                return;
+            }
+        }
+
+        // the sender lives as long as the clock
+        let _ = taken.changed().await;
+    }
+
+    /// Current simulation time
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            SimClock::RealTime {
+                origin_wall,
+                origin_sim,
+                speed,
+            } => {
+                let elapsed_ms = origin_wall.elapsed().as_secs_f64() * 1000.0 * speed;
+                *origin_sim + Duration::milliseconds(elapsed_ms as i64)
+            }
+            SimClock::Stepped(steps) => steps.lock().now,
+        }
+    }
+
+    /// Current simulation time in milliseconds since the unix epoch
+    pub fn now_ms(&self) -> u64 {
+        self.now().timestamp_millis() as u64
+    }
+}
+
+impl std::fmt::Display for SimClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimClock::RealTime { speed, .. } => write!(f, "{}x real time", speed),
+            SimClock::Stepped(steps) => write!(f, "fast-forward in {} ms steps", steps.dt_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration as WallDuration;
+
+    #[test]
+    fn runs_faster_with_a_speed_up() {
+        let clock = SimClock::new(100.0);
+        let start = clock.now();
+        std::thread::sleep(WallDuration::from_millis(20));
+
+        // at least 20 ms of wall clock time went by
+        let elapsed_ms = (clock.now() - start).num_milliseconds();
+        assert!(elapsed_ms >= 2000, "only {} ms passed", elapsed_ms);
+    }
+
+    #[test]
+    fn moves_only_when_stepped() {
+        let start = Utc::now();
+        let clock = SimClock::stepped(start, 50);
+        std::thread::sleep(WallDuration::from_millis(5));
+        assert_eq!(clock.now(), start);
+
+        clock.advance();
+        clock.advance();
+        assert_eq!(clock.now(), start + Duration::milliseconds(100));
+    }
+
+    #[test]
+    fn clones_share_the_steps() {
+        let start = Utc::now();
+        let clock = SimClock::stepped(start, 50);
+        let copy = clock.clone();
+
+        copy.advance();
+        assert_eq!(clock.now_ms(), copy.now_ms());
+        assert_eq!(clock.now(), start + Duration::milliseconds(50));
+    }
+
+    #[tokio::test]
+    async fn participants_step_together() {
+        let start = Utc::now();
+        let clock = SimClock::stepped(start, 50);
+        let first = clock.join();
+        let second = clock.join();
+
+        let waiting = tokio::spawn({
+            let clock = clock.clone();
+            async move { clock.next_step().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        assert_eq!(clock.now(), start);
+
+        // the last participant to arrive takes the step
+        clock.next_step().await;
+        waiting.await.expect("participant should finish");
+        assert_eq!(clock.now(), start + Duration::milliseconds(50));
+
+        // a participant that leaves no longer holds back the others
+        drop(first);
+        clock.next_step().await;
+        assert_eq!(clock.now(), start + Duration::milliseconds(100));
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn leaving_releases_the_waiting_participants() {
+        let start = Utc::now();
+        let clock = SimClock::stepped(start, 50);
+        let staying = clock.join();
+        let leaving = clock.join();
+
+        let waiting = tokio::spawn({
+            let clock = clock.clone();
+            async move { clock.next_step().await }
+        });
+        tokio::task::yield_now().await;
+
+        drop(leaving);
+        waiting.await.expect("participant should finish");
+        assert_eq!(clock.now(), start + Duration::milliseconds(50));
+        drop(staying);
+    }
+}
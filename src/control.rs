@@ -0,0 +1,318 @@
+//! HTTP API to inspect and command running aircraft
+//!
+//! | Method | Path                                 |                                  |
+//! | ------ | ------------------------------------ | -------------------------------- |
+//! | GET    | `/metrics`                           | Prometheus metrics               |
+//! | GET    | `/state`                             | state of every aircraft          |
+//! | GET    | `/state/{name}`                      | state of one aircraft            |
+//! | POST   | `/aircraft/{name}/pause`             | freeze movement and plan changes |
+//! | POST   | `/aircraft/{name}/resume`            | undo pause                       |
+//! | POST   | `/aircraft/{name}/abort`             | drop the current plan, stop      |
+//! | POST   | `/aircraft/{name}/teleport`          | move to `{"latitude", "longitude", "altitude_meters"}` |
+//! | POST   | `/aircraft/{name}/out-of-service`    | go silent, drop the current plan |
+//! | POST   | `/aircraft/{name}/return-to-service` | undo out of service              |
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::aircraft::Aircraft;
+use crate::backend::{CargoScanner, OrderSource, TelemetrySink};
+use crate::fleet::StatusBoard;
+use crate::metrics;
+
+/// Command injected into a running aircraft
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    AbortPlan,
+    Teleport {
+        latitude: f64,
+        longitude: f64,
+        altitude_meters: f64,
+    },
+    OutOfService,
+    ReturnToService,
+}
+
+/// Command channels of the running aircraft, keyed by aircraft name
+pub type Controls = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Command>>>>;
+
+#[derive(Debug, Deserialize)]
+struct TeleportRequest {
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    altitude_meters: f64,
+}
+
+/// Register the aircraft so it accepts commands from the control API
+pub fn attach<T, O, C>(aircraft: Aircraft<T, O, C>, controls: &Controls) -> Aircraft<T, O, C>
+where
+    T: TelemetrySink,
+    O: OrderSource,
+    C: CargoScanner,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    match controls.lock() {
+        Ok(mut controls) => {
+            controls.insert(aircraft.state.id.clone(), tx);
+        }
+        Err(_) => tracing::error!(name = %aircraft.state.id, "control registry is poisoned"),
+    }
+
+    aircraft.with_commands(rx)
+}
+
+/// Serve the control API on `addr` until the task is dropped
+pub async fn serve(
+    addr: SocketAddr,
+    status: StatusBoard,
+    controls: Controls,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let status = status.clone();
+        let controls = controls.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(status.clone(), controls.clone(), req)
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    tracing::info!(addr = %server.local_addr(), "control API listening");
+    server.await
+}
+
+fn respond(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle(
+    status: StatusBoard,
+    controls: Controls,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let response = match (method, segments.as_slice()) {
+        (Method::GET, ["metrics"]) => match metrics::render(&status) {
+            Ok(text) => respond(StatusCode::OK, text),
+            Err(e) => respond(StatusCode::INTERNAL_SERVER_ERROR, e),
+        },
+        (Method::GET, ["state"]) => state(&status, None),
+        (Method::GET, ["state", name]) => state(&status, Some(*name)),
+        (Method::POST, ["aircraft", name, command]) => {
+            match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => match parse_command(command, &body) {
+                    Ok(command) => send(&controls, name, command),
+                    Err(response) => response,
+                },
+                Err(e) => respond(StatusCode::BAD_REQUEST, format!("could not read body: {e}")),
+            }
+        }
+        _ => respond(StatusCode::NOT_FOUND, format!("{path} not found")),
+    };
+
+    Ok(response)
+}
+
+fn state(status: &StatusBoard, name: Option<&str>) -> Response<Body> {
+    let Ok(board) = status.lock() else {
+        return respond(StatusCode::INTERNAL_SERVER_ERROR, "status board is poisoned".to_string());
+    };
+
+    let json = match name {
+        None => serde_json::to_string(&*board),
+        Some(name) => match board.get(name) {
+            Some(aircraft) => serde_json::to_string(aircraft),
+            None => return respond(StatusCode::NOT_FOUND, format!("unknown aircraft {name}")),
+        },
+    };
+
+    match json {
+        Ok(json) => respond(StatusCode::OK, json),
+        Err(e) => respond(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+fn parse_command(command: &str, body: &[u8]) -> Result<Command, Response<Body>> {
+    let command = match command {
+        "pause" => Command::Pause,
+        "resume" => Command::Resume,
+        "abort" => Command::AbortPlan,
+        "out-of-service" => Command::OutOfService,
+        "return-to-service" => Command::ReturnToService,
+        "teleport" => {
+            let request = serde_json::from_slice::<TeleportRequest>(body).map_err(|e| {
+                respond(StatusCode::BAD_REQUEST, format!("invalid teleport request: {e}"))
+            })?;
+
+            Command::Teleport {
+                latitude: request.latitude,
+                longitude: request.longitude,
+                altitude_meters: request.altitude_meters,
+            }
+        }
+        other => {
+            return Err(respond(StatusCode::NOT_FOUND, format!("unknown command {other}")));
+        }
+    };
+
+    Ok(command)
+}
+
+fn send(controls: &Controls, name: &str, command: Command) -> Response<Body> {
+    let Ok(controls) = controls.lock() else {
+        return respond(StatusCode::INTERNAL_SERVER_ERROR, "control registry is poisoned".to_string());
+    };
+
+    let Some(tx) = controls.get(name) else {
+        return respond(StatusCode::NOT_FOUND, format!("unknown aircraft {name}"));
+    };
+
+    tracing::info!(name = %name, ?command, "control command");
+    match tx.send(command) {
+        Ok(_) => respond(StatusCode::ACCEPTED, String::new()),
+        Err(_) => respond(StatusCode::GONE, format!("aircraft {name} is not running")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+    use crate::Activity;
+
+    /// A board with Mantis on it, and the command queue of Mantis
+    fn fleet() -> (StatusBoard, Controls, mpsc::UnboundedReceiver<Command>) {
+        let status = StatusBoard::default();
+        let mantis = testing::aircraft_status(Activity::Idle);
+        status.lock().unwrap().insert("Mantis".to_string(), mantis);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let controls = Controls::default();
+        controls.lock().unwrap().insert("Mantis".to_string(), tx);
+        (status, controls, rx)
+    }
+
+    async fn request(
+        status: &StatusBoard,
+        controls: &Controls,
+        method: Method,
+        path: &str,
+        body: Body,
+    ) -> (StatusCode, String) {
+        let req = Request::builder().method(method).uri(path).body(body).expect("valid request");
+        let response = handle(status.clone(), controls.clone(), req).await.expect("infallible");
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.expect("response body");
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    async fn post(
+        status: &StatusBoard,
+        controls: &Controls,
+        path: &str,
+        body: Body,
+    ) -> (StatusCode, String) {
+        request(status, controls, Method::POST, path, body).await
+    }
+
+    async fn get(status: &StatusBoard, controls: &Controls, path: &str) -> (StatusCode, String) {
+        request(status, controls, Method::GET, path, Body::empty()).await
+    }
+
+    #[tokio::test]
+    async fn accepts_commands_for_running_aircraft() {
+        let (status, controls, mut rx) = fleet();
+        let (code, _) = post(&status, &controls, "/aircraft/Mantis/pause", Body::empty()).await;
+        assert_eq!(code, StatusCode::ACCEPTED);
+
+        let teleport = Body::from(r#"{"latitude": 52.37, "longitude": 4.91}"#);
+        let (code, _) = post(&status, &controls, "/aircraft/Mantis/teleport", teleport).await;
+        assert_eq!(code, StatusCode::ACCEPTED);
+
+        assert_eq!(rx.try_recv().ok(), Some(Command::Pause));
+        let teleported = Command::Teleport {
+            latitude: 52.37,
+            longitude: 4.91,
+            altitude_meters: 0.0,
+        };
+        assert_eq!(rx.try_recv().ok(), Some(teleported));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_aircraft_and_commands() {
+        let (status, controls, _rx) = fleet();
+        let (code, _) = post(&status, &controls, "/aircraft/Hornet/pause", Body::empty()).await;
+        assert_eq!(code, StatusCode::NOT_FOUND);
+
+        let (code, body) = post(&status, &controls, "/aircraft/Mantis/loop", Body::empty()).await;
+        assert_eq!(code, StatusCode::NOT_FOUND);
+        assert_eq!(body, "unknown command loop");
+
+        let (code, _) = get(&status, &controls, "/fleet").await;
+        assert_eq!(code, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reports_aircraft_that_stopped_running() {
+        let (status, controls, rx) = fleet();
+        drop(rx);
+        let (code, _) = post(&status, &controls, "/aircraft/Mantis/abort", Body::empty()).await;
+        assert_eq!(code, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_teleport_requests() {
+        let (status, controls, mut rx) = fleet();
+        let body = Body::from(r#"{"latitude": 52.37}"#);
+        let (code, body) = post(&status, &controls, "/aircraft/Mantis/teleport", body).await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert!(body.starts_with("invalid teleport request"), "{body}");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn reports_bodies_that_cannot_be_read() {
+        let (status, controls, mut rx) = fleet();
+        let (sender, body) = Body::channel();
+        sender.abort();
+
+        let (code, body) = post(&status, &controls, "/aircraft/Mantis/teleport", body).await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert!(body.starts_with("could not read body"), "{body}");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn serves_the_state_of_one_aircraft() {
+        let (status, controls, _rx) = fleet();
+        let (code, body) = get(&status, &controls, "/state/Mantis").await;
+        assert_eq!(code, StatusCode::OK);
+
+        let aircraft: serde_json::Value = serde_json::from_str(&body).expect("state is JSON");
+        assert_eq!(aircraft["position"]["latitude"], 52.3676);
+
+        let (code, body) = get(&status, &controls, "/state").await;
+        assert_eq!(code, StatusCode::OK);
+        let board: serde_json::Value = serde_json::from_str(&body).expect("state is JSON");
+        assert!(board.get("Mantis").is_some());
+
+        let (code, _) = get(&status, &controls, "/state/Hornet").await;
+        assert_eq!(code, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,175 @@
+//! Errors of the simulator
+//!
+//! Requests to the backend services fail with a [`BackendError`], NETRID
+//!  frames that cannot be built fail with an [`EncodeError`]. Neither stops
+//!  an aircraft: the failure is reported and the aircraft carries on with
+//!  its next tick.
+
+use hyper::StatusCode;
+use packed_struct::PackingError;
+
+use crate::uas_id::SerialError;
+
+/// Source of a [`BackendError`]
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// A request to a backend service failed
+///
+/// `endpoint` is the same label as the one of the `http_request_duration_seconds`
+///  metric, e.g. `login` or `plans`.
+#[derive(Debug)]
+pub enum BackendError {
+    /// the request could not be sent, or the response could not be read
+    Transport { endpoint: &'static str, source: Source },
+    /// the service refused the credentials of the aircraft
+    Unauthorized { endpoint: &'static str, status: StatusCode },
+    /// the service answered with an unexpected status
+    Status { endpoint: &'static str, status: StatusCode },
+    /// the request or the response body could not be (de)serialized
+    Payload { endpoint: &'static str, source: Source },
+    /// the circuit breaker of the endpoint is open, no request was sent
+    CircuitOpen { endpoint: &'static str },
+    /// no response within the timeout
+    Timeout { endpoint: &'static str },
+}
+
+impl BackendError {
+    /// Error for a response with a non-success `status`
+    pub fn from_status(endpoint: &'static str, status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                BackendError::Unauthorized { endpoint, status }
+            }
+            _ => BackendError::Status { endpoint, status },
+        }
+    }
+
+    pub fn transport(endpoint: &'static str, source: impl Into<Source>) -> Self {
+        BackendError::Transport {
+            endpoint,
+            source: source.into(),
+        }
+    }
+
+    pub fn payload(endpoint: &'static str, source: impl Into<Source>) -> Self {
+        BackendError::Payload {
+            endpoint,
+            source: source.into(),
+        }
+    }
+
+    /// The endpoint the request was sent to
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            BackendError::Transport { endpoint, .. }
+            | BackendError::Unauthorized { endpoint, .. }
+            | BackendError::Status { endpoint, .. }
+            | BackendError::Payload { endpoint, .. }
+            | BackendError::CircuitOpen { endpoint }
+            | BackendError::Timeout { endpoint } => endpoint,
+        }
+    }
+
+    /// If the service refused the credentials, and logging in again may help
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, BackendError::Unauthorized { .. })
+    }
+
+    /// If the service may be unavailable and the call is worth retrying
+    ///  later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            BackendError::Transport { .. }
+            | BackendError::CircuitOpen { .. }
+            | BackendError::Timeout { .. } => true,
+            BackendError::Status { status, .. } => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            BackendError::Unauthorized { .. } | BackendError::Payload { .. } => false,
+        }
+    }
+
+    /// The HTTP status of the response, if there was one
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            BackendError::Unauthorized { status, .. } | BackendError::Status { status, .. } => {
+                Some(*status)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Transport { endpoint, source } => {
+                write!(f, "{endpoint}: request failed: {source}")
+            }
+            BackendError::Unauthorized { endpoint, status } => {
+                write!(f, "{endpoint}: unauthorized ({status})")
+            }
+            BackendError::Status { endpoint, status } => {
+                write!(f, "{endpoint}: unexpected status {status}")
+            }
+            BackendError::Payload { endpoint, source } => {
+                write!(f, "{endpoint}: invalid payload: {source}")
+            }
+            BackendError::CircuitOpen { endpoint } => {
+                write!(f, "{endpoint}: circuit breaker is open")
+            }
+            BackendError::Timeout { endpoint } => write!(f, "{endpoint}: timed out"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BackendError::Transport { source, .. } | BackendError::Payload { source, .. } => {
+                Some(source.as_ref())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A NETRID frame could not be built
+#[derive(Debug)]
+pub enum EncodeError {
+    /// the identifier does not fit the UAS id field
+    Identifier(String),
+    /// the serial number is not a valid CTA-2063-A serial number
+    Serial(SerialError),
+    /// a value is out of the range the message can carry
+    Field { field: &'static str, value: String },
+    /// a message could not be packed
+    Pack {
+        message: &'static str,
+        source: PackingError,
+    },
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::Identifier(id) => write!(f, "identifier {id} does not fit the UAS id"),
+            EncodeError::Serial(e) => write!(f, "invalid serial number: {e}"),
+            EncodeError::Field { field, value } => write!(f, "could not encode {field} {value}"),
+            EncodeError::Pack { message, source } => {
+                write!(f, "could not pack {message}: {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncodeError::Pack { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use svc_atc_client_rest::types::*;
+
+use crate::aircraft::{Aircraft, AircraftConfig};
+use crate::backend::amqp::{AmqpOrdersConfig, OrderFeed};
+use crate::backend::{CargoScanner, OrderSource, TelemetrySink};
+use crate::battery::{Battery, BatteryOptions};
+use crate::checkpoint::Checkpoint;
+use crate::clock::SimClock;
+use crate::control::{self, Controls};
+use crate::kinematics::KinematicLimits;
+use crate::link::LinkOptions;
+use crate::phase::PhaseOptions;
+use crate::profile::PerformanceProfile;
+use crate::retry::RetryPolicy;
+use crate::scenario::{Rates, SimOptions};
+use crate::shutdown::{Shutdown, ShutdownReport};
+use crate::wind::WindModel;
+use crate::Activity;
+
+/// Latest known status of each aircraft, keyed by aircraft name
+pub type StatusBoard = Arc<Mutex<HashMap<String, AircraftStatus>>>;
+
+/// Snapshot of a single aircraft, as reported by the fleet status and the
+///  control API, and saved to checkpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AircraftStatus {
+    pub timestamp: DateTime<Utc>,
+    pub activity: Activity,
+    pub operational: bool,
+    pub paused: bool,
+    pub position: PointZ,
+    pub ground_velocity_m_s: f64,
+    pub vertical_velocity_m_s: f64,
+    pub track_angle_deg: f64,
+    #[serde(default)]
+    pub airspeed_m_s: f64,
+    #[serde(default)]
+    pub heading_deg: f64,
+    /// cruise speed of the current plan
+    #[serde(default)]
+    pub target_ground_velocity_m_s: f64,
+    /// altitude the current phase was entered at, the current altitude if
+    ///  missing
+    #[serde(default)]
+    pub phase_altitude_m: Option<f64>,
+    /// previous point of the current plan, the leg runs from there
+    #[serde(default)]
+    pub leg_origin: Option<PointZ>,
+    pub current_plan: Option<FlightPlan>,
+    pub queued_plans: Vec<FlightPlan>,
+    /// cargo ids of the parcels on board
+    #[serde(default)]
+    pub onboard: Vec<String>,
+    #[serde(default)]
+    pub payload_kg: f64,
+    #[serde(default)]
+    pub battery: Option<Battery>,
+    /// landing short of the target after breaching the battery reserve
+    #[serde(default)]
+    pub diverted: bool,
+}
+
+/// Everything shared by the aircraft of a fleet
+#[derive(Clone)]
+pub struct FleetContext {
+    /// a single clock for the fleet, the aircraft take its steps together
+    pub clock: SimClock,
+    pub rates: Rates,
+    pub options: SimOptions,
+    pub order_feed: Option<OrderFeed>,
+    pub retry: RetryPolicy,
+    pub links: LinkOptions,
+    pub kinematics: KinematicLimits,
+    pub phases: PhaseOptions,
+    /// energy is not simulated without it
+    pub battery: Option<BatteryOptions>,
+    /// performance profiles by name, aircraft without one fly the default
+    pub profiles: HashMap<String, PerformanceProfile>,
+    pub wind: WindModel,
+    pub status: StatusBoard,
+    pub controls: Controls,
+    /// lands the aircraft and ends [`run`] once it fires
+    pub shutdown: Option<Shutdown>,
+    /// aircraft found in it continue where they were
+    pub resume: Option<Checkpoint>,
+}
+
+impl FleetContext {
+    pub fn new(
+        clock: SimClock,
+        rates: Rates,
+        options: SimOptions,
+        order_feed: Option<AmqpOrdersConfig>,
+        retry: RetryPolicy,
+        links: LinkOptions,
+    ) -> Self {
+        FleetContext {
+            clock,
+            rates,
+            options,
+            order_feed: order_feed.map(OrderFeed::new),
+            retry,
+            links,
+            kinematics: KinematicLimits::default(),
+            phases: PhaseOptions::default(),
+            battery: None,
+            profiles: HashMap::new(),
+            wind: WindModel::default(),
+            status: Arc::new(Mutex::new(HashMap::new())),
+            controls: Arc::new(Mutex::new(HashMap::new())),
+            shutdown: None,
+            resume: None,
+        }
+    }
+
+    /// Fly every aircraft within `limits`
+    pub fn with_kinematic_limits(mut self, limits: KinematicLimits) -> Self {
+        self.kinematics = limits;
+        self
+    }
+
+    /// Fly the phases of every plan according to `options`
+    pub fn with_phase_options(mut self, options: PhaseOptions) -> Self {
+        self.phases = options;
+        self
+    }
+
+    /// Give every aircraft a battery that draws power according to
+    ///  `options`
+    pub fn with_battery(mut self, options: BatteryOptions) -> Self {
+        self.battery = Some(options);
+        self
+    }
+
+    /// Let aircraft select their performance profile from `profiles`
+    pub fn with_profiles(mut self, profiles: HashMap<String, PerformanceProfile>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Have every aircraft fly through `wind`, with gusts of its own
+    pub fn with_wind(mut self, wind: WindModel) -> Self {
+        self.wind = wind;
+        self
+    }
+
+    /// Land every aircraft once `shutdown` fires
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Restore the aircraft saved in `checkpoint`
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
+    }
+
+    /// Build an aircraft wired to the order feed and the control API
+    pub async fn aircraft<T, O, C>(
+        &self,
+        config: AircraftConfig,
+        telemetry: T,
+        orders: O,
+        cargo: C,
+    ) -> Aircraft<T, O, C>
+    where
+        T: TelemetrySink,
+        O: OrderSource,
+        C: CargoScanner,
+    {
+        let saved = self
+            .resume
+            .as_ref()
+            .and_then(|checkpoint| checkpoint.aircraft.get(&config.name))
+            .cloned();
+
+        let wind = self.wind.for_aircraft(&config.name);
+        let profile = config
+            .profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .copied()
+            .unwrap_or_default();
+        let clock = self.clock.clone();
+        let mut aircraft = Aircraft::new(config, clock, self.rates, telemetry, orders, cargo)
+            .with_wind(wind)
+            .with_retry_policy(self.retry)
+            .with_link_options(self.links)
+            .with_kinematic_limits(self.kinematics)
+            .with_phase_options(self.phases)
+            .with_profile(profile);
+        if let Some(options) = self.battery {
+            aircraft = aircraft.with_battery(options);
+        }
+        if let Some(ref shutdown) = self.shutdown {
+            aircraft = aircraft.with_shutdown(shutdown.clone());
+        }
+        if let Some(status) = saved {
+            tracing::info!(
+                name = %aircraft.state.id,
+                activity = ?status.activity,
+                session = status.current_plan.as_ref().map(|p| p.session_id.as_str()).unwrap_or("-"),
+                queued = status.queued_plans.len(),
+                onboard = status.onboard.len(),
+                "resuming aircraft"
+            );
+            aircraft = aircraft.with_restored(status);
+        }
+        let aircraft = attach_order_feed(aircraft, self.order_feed.as_ref()).await;
+        control::attach(aircraft, &self.controls)
+    }
+}
+
+/// Spawn one simulation task per aircraft and periodically report
+///  the combined status of the fleet
+///
+/// Returns the final state of each aircraft once all of them shut down.
+pub async fn run<T, O, C>(
+    context: FleetContext,
+    aircraft: Vec<AircraftConfig>,
+    telemetry: T,
+    orders: O,
+    cargo: C,
+) -> Vec<ShutdownReport>
+where
+    T: TelemetrySink + Clone + 'static,
+    O: OrderSource + Clone + 'static,
+    C: CargoScanner + Clone + 'static,
+{
+    tracing::info!(aircraft = aircraft.len(), clock = %context.clock, "starting fleet");
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for config in aircraft {
+        let aircraft = context
+            .aircraft(config, telemetry.clone(), orders.clone(), cargo.clone())
+            .await;
+
+        tasks.spawn(aircraft.run(context.options.tick_ms, context.status.clone()));
+    }
+
+    tokio::spawn(report(context.status.clone(), context.options.status_interval_ms));
+
+    let mut reports = vec![];
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(report) => reports.push(report),
+            Err(e) => tracing::error!(error = %e, "aircraft task terminated"),
+        }
+    }
+
+    tracing::info!("all aircraft terminated");
+    reports
+}
+
+/// Subscribe the aircraft to its flight plan queue, if configured
+///
+/// The aircraft falls back to polling when the subscription fails.
+pub async fn attach_order_feed<T, O, C>(
+    aircraft: Aircraft<T, O, C>,
+    feed: Option<&OrderFeed>,
+) -> Aircraft<T, O, C>
+where
+    T: TelemetrySink,
+    O: OrderSource,
+    C: CargoScanner,
+{
+    let Some(feed) = feed else {
+        return aircraft;
+    };
+
+    match feed.subscribe(aircraft.uuid(), &aircraft.state.id).await {
+        Ok(rx) => aircraft.with_order_feed(rx, feed.config().fallback_poll_interval_ms),
+        Err(e) => {
+            tracing::warn!(
+                name = %aircraft.state.id,
+                error = %e,
+                "could not subscribe to flight plans, polling instead"
+            );
+            aircraft
+        }
+    }
+}
+
+/// Print a summary of the fleet at a fixed interval
+async fn report(status: StatusBoard, interval_ms: u64) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(interval_ms));
+
+    loop {
+        interval.tick().await;
+
+        let Ok(board) = status.lock() else {
+            tracing::error!("status board is poisoned, stopping report");
+            return;
+        };
+
+        let airborne = board
+            .values()
+            .filter(|s| s.activity.is_airborne())
+            .count();
+        let out_of_service = board
+            .values()
+            .filter(|s| s.activity == Activity::OutOfService)
+            .count();
+        let on_ground = board.len() - airborne - out_of_service;
+        let queued: usize = board.values().map(|s| s.queued_plans.len()).sum();
+
+        tracing::info!(
+            aircraft = board.len(),
+            on_ground,
+            airborne,
+            out_of_service,
+            queued,
+            "fleet status"
+        );
+
+        let mut names: Vec<&String> = board.keys().collect();
+        names.sort();
+        for name in names {
+            let s = &board[name];
+            tracing::debug!(
+                name = %name,
+                activity = ?s.activity,
+                session = s.current_plan.as_ref().map(|p| p.session_id.as_str()).unwrap_or("-"),
+                latitude = s.position.latitude,
+                longitude = s.position.longitude,
+                altitude_meters = s.position.altitude_meters,
+                queued = s.queued_plans.len(),
+                "aircraft status"
+            );
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Speed, climb and heading changes within the limits of the airframe
+//!
+//! Flight plans only give the points to fly through and, by their
+//!  timeslots, a cruise speed. Instead of jumping to the velocity towards
+//!  the next point, the aircraft ramps its speed, vertical speed and heading
+//!  towards it with bounded acceleration, jerk and turn rate, and slows down
+//!  in time to stop at the target. What it steers towards depends on the
+//!  flight phase, see [`crate::phase`].
+//!
+//! Speed and heading are flown through the air. The guidance picks the
+//!  ground velocity that leads along the leg to the next point, and crabs
+//!  into the mean wind to make it good, see [`crate::wind`].
+
+use geo::point;
+use geo::prelude::*;
+use serde::Deserialize;
+
+use crate::phase::{self, Activity, PhaseOptions};
+use crate::wind::Velocity;
+use crate::State;
+
+/// Performance limits of the aircraft
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct KinematicLimits {
+    /// horizontal acceleration in m/s²
+    pub max_acceleration_m_s2: f64,
+    /// horizontal deceleration in m/s²
+    pub max_deceleration_m_s2: f64,
+    /// change of the horizontal and vertical acceleration in m/s³
+    pub max_jerk_m_s3: f64,
+    pub max_turn_rate_deg_s: f64,
+    pub max_climb_rate_m_s: f64,
+    pub max_descent_rate_m_s: f64,
+    /// change of the vertical speed in m/s²
+    pub max_vertical_acceleration_m_s2: f64,
+}
+
+impl Default for KinematicLimits {
+    fn default() -> Self {
+        KinematicLimits {
+            max_acceleration_m_s2: 2.0,
+            max_deceleration_m_s2: 2.5,
+            max_jerk_m_s3: 2.0,
+            max_turn_rate_deg_s: 30.0,
+            max_climb_rate_m_s: 5.0,
+            max_descent_rate_m_s: 3.0,
+            max_vertical_acceleration_m_s2: 1.0,
+        }
+    }
+}
+
+/// Distance in meters within which the last point of a plan is reached
+pub const TARGET_RADIUS_M: f64 = 5.0;
+
+/// Time ahead along the leg the guidance aims at to get back onto it
+const LOOKAHEAD_S: f64 = 5.0;
+
+/// Shortest distance ahead along the leg the guidance aims at
+const MIN_LOOKAHEAD_M: f64 = 20.0;
+
+/// Mean earth radius in meters, as used by the haversine functions of geo
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Airspeed below which the heading is kept
+const MIN_HEADING_SPEED_M_S: f64 = 0.5;
+
+/// Steer the aircraft towards the next point of its plan for `elapsed_s`
+///
+/// Updates the airspeed, vertical speed and heading, correcting for `wind`;
+///  adding the actual wind and moving the aircraft is up to the caller. On
+///  the ground and in hold the aircraft comes to rest, takeoff and landing
+///  are vertical, the approach is flown level at the approach speed.
+pub fn steer(
+    current_ms: u64,
+    state: &mut State,
+    limits: &KinematicLimits,
+    phases: &PhaseOptions,
+    wind: Velocity,
+    elapsed_s: f64,
+) {
+    // called on every tick, only log once in a while
+    let log_tick = state.tick_log.allow(current_ms);
+    if log_tick {
+        tracing::trace!(current_ms, position = ?state.position, "adjusting velocity");
+    }
+
+    let Some(ref plan) = state.current_plan else {
+        return;
+    };
+
+    let Some(next_point) = plan.path.first() else {
+        tracing::trace!(current_ms, "no more points in plan");
+        return;
+    };
+
+    if elapsed_s <= 0.0 {
+        return;
+    }
+
+    let p1 = point!(x: state.position.longitude, y: state.position.latitude);
+    let p2 = point!(x: next_point.longitude, y: next_point.latitude);
+    let distance = p1.haversine_distance(&p2);
+    let last_point = plan.path.len() == 1;
+    let climb = phase::leg_altitude_m(state, phases).unwrap_or(next_point.altitude_meters)
+        - state.position.altitude_meters;
+
+    let cruise_speed = match state.activity {
+        Activity::Climb | Activity::Cruise | Activity::Descent => state.target_ground_velocity_m_s,
+        Activity::Approach => state.target_ground_velocity_m_s.min(phases.approach_speed_m_s),
+        _ => 0.0,
+    };
+
+    // cruise, and slow down in time to stop at the target
+    let mut target_speed = cruise_speed;
+    if last_point {
+        target_speed = target_speed.min(stopping_speed(distance, limits));
+    }
+
+    // the air velocity that makes good the wanted ground velocity
+    let bearing = p1.haversine_bearing(p2);
+    let track = leg_track(state, distance, bearing);
+    let air = Velocity::towards(target_speed, track) - wind;
+
+    (state.airspeed_m_s, state.acceleration_m_s2) = ramp(
+        state.airspeed_m_s,
+        state.acceleration_m_s2,
+        air.speed_m_s(),
+        (limits.max_acceleration_m_s2, limits.max_deceleration_m_s2),
+        limits.max_jerk_m_s3,
+        elapsed_s,
+    );
+
+    let target_vertical_speed = match state.activity {
+        Activity::Takeoff => phases.takeoff_climb_rate_m_s,
+        Activity::Landing => -phases.landing_descent_rate_m_s,
+        // hold the approach height
+        Activity::Approach => climb,
+        Activity::Climb | Activity::Cruise | Activity::Descent => {
+            // reach the altitude of the next point along with its position
+            let speed = target_speed.max(state.ground_velocity_m_s);
+            let time_to_next_point_s = if speed > 0.0 {
+                (distance / speed).max(elapsed_s)
+            } else {
+                f64::INFINITY
+            };
+            climb / time_to_next_point_s
+        }
+        _ => 0.0,
+    }
+    .clamp(-limits.max_descent_rate_m_s, limits.max_climb_rate_m_s);
+
+    (state.vertical_velocity_m_s, state.vertical_acceleration_m_s2) = ramp(
+        state.vertical_velocity_m_s,
+        state.vertical_acceleration_m_s2,
+        target_vertical_speed,
+        (limits.max_vertical_acceleration_m_s2, limits.max_vertical_acceleration_m_s2),
+        limits.max_jerk_m_s3,
+        elapsed_s,
+    );
+
+    // the target moves when levelling off, do not run past the rate limits
+    //  meanwhile
+    state.vertical_velocity_m_s = state
+        .vertical_velocity_m_s
+        .clamp(-limits.max_descent_rate_m_s, limits.max_climb_rate_m_s);
+
+    // turn the shortest way, at most at the turn rate; hovering in calm
+    //  air any heading will do
+    if air.speed_m_s() >= MIN_HEADING_SPEED_M_S {
+        let turn = (air.direction_deg() - state.heading_deg + 540.0).rem_euclid(360.0) - 180.0;
+        let max_turn = limits.max_turn_rate_deg_s * elapsed_s;
+        state.heading_deg = (state.heading_deg + turn.clamp(-max_turn, max_turn)).rem_euclid(360.0);
+    }
+
+    if log_tick {
+        tracing::debug!(
+            current_ms,
+            ?next_point,
+            distance,
+            airspeed_m_s = state.airspeed_m_s,
+            vertical_velocity_m_s = state.vertical_velocity_m_s,
+            heading_deg = state.heading_deg,
+            wind_m_s = wind.speed_m_s(),
+            "adjusted velocity"
+        );
+    }
+}
+
+/// Descend vertically onto the current position for `elapsed_s`
+///
+/// For landings without a plan, like a plan that ended in the air or a
+///  shutdown in hold. The aircraft heads into `wind` to hold its position.
+pub fn descend(
+    state: &mut State,
+    limits: &KinematicLimits,
+    phases: &PhaseOptions,
+    wind: Velocity,
+    elapsed_s: f64,
+) {
+    if elapsed_s <= 0.0 {
+        return;
+    }
+
+    let air = Velocity::default() - wind;
+    (state.airspeed_m_s, state.acceleration_m_s2) = ramp(
+        state.airspeed_m_s,
+        state.acceleration_m_s2,
+        air.speed_m_s(),
+        (limits.max_acceleration_m_s2, limits.max_deceleration_m_s2),
+        limits.max_jerk_m_s3,
+        elapsed_s,
+    );
+
+    let target_vertical_speed = (-phases.landing_descent_rate_m_s)
+        .clamp(-limits.max_descent_rate_m_s, limits.max_climb_rate_m_s);
+    (state.vertical_velocity_m_s, state.vertical_acceleration_m_s2) = ramp(
+        state.vertical_velocity_m_s,
+        state.vertical_acceleration_m_s2,
+        target_vertical_speed,
+        (limits.max_vertical_acceleration_m_s2, limits.max_vertical_acceleration_m_s2),
+        limits.max_jerk_m_s3,
+        elapsed_s,
+    );
+
+    if air.speed_m_s() >= MIN_HEADING_SPEED_M_S {
+        let turn = (air.direction_deg() - state.heading_deg + 540.0).rem_euclid(360.0) - 180.0;
+        let max_turn = limits.max_turn_rate_deg_s * elapsed_s;
+        state.heading_deg = (state.heading_deg + turn.clamp(-max_turn, max_turn)).rem_euclid(360.0);
+    }
+}
+
+/// Ground track that leads onto the leg to the next point
+///
+/// Aims at the leg a few seconds ahead of the aircraft, so drift off the
+///  leg is corrected; close to the point, or without a leg, heads straight
+///  for it at `bearing`.
+fn leg_track(state: &State, distance: f64, bearing: f64) -> f64 {
+    let lookahead = (state.ground_velocity_m_s * LOOKAHEAD_S).max(MIN_LOOKAHEAD_M);
+    let (Some(origin), Some(next_point)) = (
+        state.leg_origin.as_ref(),
+        state.current_plan.as_ref().and_then(|p| p.path.first()),
+    ) else {
+        return bearing;
+    };
+
+    let o = point!(x: origin.longitude, y: origin.latitude);
+    let n = point!(x: next_point.longitude, y: next_point.latitude);
+    let p = point!(x: state.position.longitude, y: state.position.latitude);
+    if distance <= lookahead || o.haversine_distance(&n) < 1.0 {
+        return bearing;
+    }
+
+    // distance right of the leg, along the great circle
+    let leg_bearing = o.haversine_bearing(n);
+    let angle = (o.haversine_bearing(p) - leg_bearing).to_radians();
+    let from_origin = o.haversine_distance(&p) / EARTH_RADIUS_M;
+    let cross_track = (from_origin.sin() * angle.sin()).asin() * EARTH_RADIUS_M;
+
+    (leg_bearing - cross_track.atan2(lookahead).to_degrees()).rem_euclid(360.0)
+}
+
+/// Distance in meters within which the next point counts as reached
+///
+/// Intermediate points are flown by: the aircraft starts turning towards
+///  the following point one turn radius ahead of them. The last point is
+///  flown to.
+pub fn arrival_radius_m(state: &State, limits: &KinematicLimits) -> f64 {
+    let last_point = match state.current_plan {
+        Some(ref plan) => plan.path.len() <= 1,
+        None => true,
+    };
+
+    let turn_rate_rad_s = limits.max_turn_rate_deg_s.to_radians();
+    if last_point || turn_rate_rad_s <= 0.0 {
+        return TARGET_RADIUS_M;
+    }
+
+    (state.ground_velocity_m_s / turn_rate_rad_s).max(TARGET_RADIUS_M)
+}
+
+/// Highest speed from which the aircraft still stops within `distance` m
+///
+/// Accounts for the time the deceleration takes to build up under the
+///  jerk limit.
+fn stopping_speed(distance: f64, limits: &KinematicLimits) -> f64 {
+    let deceleration = limits.max_deceleration_m_s2;
+    if deceleration <= 0.0 || limits.max_jerk_m_s3 <= 0.0 {
+        return f64::INFINITY;
+    }
+
+    // distance = v² / 2a + v * a / j, solved for v
+    let ramp_s = deceleration / limits.max_jerk_m_s3;
+    deceleration * ((ramp_s * ramp_s + 2.0 * distance / deceleration).sqrt() - ramp_s)
+}
+
+/// Move `value` towards `target` over `elapsed_s`
+///
+/// `rate` is the current rate of change of `value`, bounded by `max_rise`
+///  and `max_fall` and changing at most by `max_jerk` per second. The
+///  rate is eased off before the target so `value` settles on it without
+///  overshooting. Returns the new value and rate.
+fn ramp(
+    value: f64,
+    rate: f64,
+    target: f64,
+    (max_rise, max_fall): (f64, f64),
+    max_jerk: f64,
+    elapsed_s: f64,
+) -> (f64, f64) {
+    let error = target - value;
+
+    // the highest rate that can still be eased off to zero at the target
+    let easing = (2.0 * max_jerk * error.abs()).sqrt();
+    let wanted = if error >= 0.0 {
+        easing.min(max_rise)
+    } else {
+        -easing.min(max_fall)
+    };
+
+    let max_change = max_jerk * elapsed_s;
+    let rate = rate + (wanted - rate).clamp(-max_change, max_change);
+    let next = value + rate * elapsed_s;
+
+    // reached or passed the target
+    if (target - next) * error <= 0.0 {
+        return (target, 0.0);
+    }
+
+    (next, rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::testing::{flight_plan, state};
+
+    const DT_S: f64 = 0.05;
+
+    /// Climbing out of the origin towards the waypoint, heading east
+    fn climbing() -> State {
+        let mut plan = flight_plan(Utc::now());
+        plan.path.remove(0);
+
+        let mut state = state();
+        state.current_plan = Some(plan);
+        state.activity = Activity::Climb;
+        state.target_ground_velocity_m_s = 10.0;
+        state.heading_deg = 90.0;
+        state
+    }
+
+    #[test]
+    fn ramps_speed_heading_and_climb_within_limits() {
+        let limits = KinematicLimits::default();
+        let phases = PhaseOptions::default();
+        let mut state = climbing();
+
+        for tick in 1..=400 {
+            let (speed, vertical_speed, heading) =
+                (state.airspeed_m_s, state.vertical_velocity_m_s, state.heading_deg);
+            steer(tick * 50, &mut state, &limits, &phases, Velocity::default(), DT_S);
+
+            let change = state.airspeed_m_s - speed;
+            assert!(change <= limits.max_acceleration_m_s2 * DT_S + 1e-9);
+            assert!(-change <= limits.max_deceleration_m_s2 * DT_S + 1e-9);
+            let vertical_change = (state.vertical_velocity_m_s - vertical_speed).abs();
+            assert!(vertical_change <= limits.max_vertical_acceleration_m_s2 * DT_S + 1e-9);
+            assert!(state.vertical_velocity_m_s <= limits.max_climb_rate_m_s);
+            assert!(-state.vertical_velocity_m_s <= limits.max_descent_rate_m_s);
+            let turn = (state.heading_deg - heading + 540.0).rem_euclid(360.0) - 180.0;
+            assert!(turn.abs() <= limits.max_turn_rate_deg_s * DT_S + 1e-9);
+        }
+
+        // settled on the cruise speed, heading north for the waypoint
+        assert!((state.airspeed_m_s - 10.0).abs() < 1e-9);
+        let heading = (state.heading_deg + 180.0).rem_euclid(360.0) - 180.0;
+        assert!(heading.abs() < 1.0, "heading {}", state.heading_deg);
+        assert!(state.vertical_velocity_m_s > 0.0);
+    }
+
+    #[test]
+    fn ramp_settles_on_the_target_without_overshooting() {
+        let (mut value, mut rate) = (0.0, 0.0);
+        for _ in 0..1000 {
+            (value, rate) = ramp(value, rate, 10.0, (2.0, 2.5), 2.0, DT_S);
+            assert!(value <= 10.0);
+        }
+        assert_eq!((value, rate), (10.0, 0.0));
+
+        for _ in 0..1000 {
+            (value, rate) = ramp(value, rate, -3.0, (2.0, 2.5), 2.0, DT_S);
+            assert!(value >= -3.0);
+        }
+        assert_eq!((value, rate), (-3.0, 0.0));
+    }
+
+    #[test]
+    fn stops_within_the_stopping_distance() {
+        let limits = KinematicLimits::default();
+        assert_eq!(stopping_speed(0.0, &limits), 0.0);
+
+        for distance in [10.0, 100.0, 1000.0] {
+            let (mut speed, mut rate) = (stopping_speed(distance, &limits), 0.0);
+            let mut travelled = 0.0;
+            while speed > 0.0 {
+                let decelerations = (limits.max_acceleration_m_s2, limits.max_deceleration_m_s2);
+                (speed, rate) = ramp(speed, rate, 0.0, decelerations, limits.max_jerk_m_s3, DT_S);
+                travelled += speed * DT_S;
+            }
+
+            assert!(travelled <= distance + 1.0, "{travelled} m to stop within {distance} m");
+        }
+    }
+
+    #[test]
+    fn flies_by_intermediate_points() {
+        let limits = KinematicLimits::default();
+        let mut state = climbing();
+        state.ground_velocity_m_s = 10.0;
+        let radius = arrival_radius_m(&state, &limits);
+        assert!((radius - 10.0 / limits.max_turn_rate_deg_s.to_radians()).abs() < 1e-9);
+
+        // the last point is flown to
+        state.current_plan.as_mut().unwrap().path.remove(0);
+        assert_eq!(arrival_radius_m(&state, &limits), TARGET_RADIUS_M);
+    }
+}
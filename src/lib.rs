@@ -0,0 +1,35 @@
+//! Carrier aircraft simulation
+//!
+//! Simulates aircraft flying the flight plans handed out by svc-atc, while
+//!  reporting NETRID telemetry to svc-telemetry and scanning parcels with
+//!  svc-cargo. The services are reached through the traits in [`backend`],
+//!  so the simulation can be embedded in test harnesses with in-memory
+//!  backends.
+
+pub mod aircraft;
+pub mod backend;
+pub mod batch;
+pub mod battery;
+pub mod checkpoint;
+pub mod clock;
+pub mod control;
+pub mod error;
+pub mod fleet;
+pub mod kinematics;
+pub mod link;
+pub mod logging;
+pub mod metrics;
+pub mod mock;
+pub mod orders;
+pub mod phase;
+pub mod profile;
+pub mod retry;
+pub mod scenario;
+pub mod shutdown;
+pub mod telemetry;
+#[cfg(test)]
+mod testing;
+pub mod uas_id;
+pub mod wind;
+
+pub use aircraft::{Activity, Aircraft, AircraftConfig, State};
@@ -0,0 +1,118 @@
+//! Cargo link: parcel scans
+
+use std::collections::VecDeque;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use super::{LinkSettings, Snapshot};
+use crate::backend::CargoScanner;
+use crate::metrics::{metrics, result_label};
+use crate::orders::ParcelScan;
+use crate::retry::Backoff;
+
+/// Message from the physics loop to the cargo link
+#[derive(Debug)]
+pub enum CargoMessage {
+    /// a parcel was loaded or unloaded
+    Scan(ParcelScan),
+    /// answered once the messages queued before it and the latest snapshot
+    ///  were handled
+    Flush(oneshot::Sender<()>),
+}
+
+/// Reports the parcel scans of an aircraft to svc-cargo
+///
+/// Scans are reported in order. While svc-cargo is unavailable they are
+///  kept and retried with the first snapshot after the backoff elapsed.
+pub struct CargoLink<C> {
+    scanner: C,
+    identifier: String,
+    scanner_id: String,
+    backoff: Backoff,
+    operational: bool,
+    /// parcel scans not reported yet
+    pending: VecDeque<ParcelScan>,
+}
+
+impl<C: CargoScanner> CargoLink<C> {
+    pub fn new(scanner: C, settings: &LinkSettings) -> Self {
+        CargoLink {
+            scanner,
+            identifier: settings.identifier.clone(),
+            scanner_id: settings.scanner_id.clone(),
+            backoff: Backoff::new(settings.retry),
+            operational: true,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Handle snapshots and messages until the physics loop is dropped
+    pub async fn run(
+        mut self,
+        mut snapshots: watch::Receiver<Snapshot>,
+        mut rx: mpsc::UnboundedReceiver<CargoMessage>,
+    ) {
+        loop {
+            tokio::select! {
+                changed = snapshots.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+
+                    self.operational = snapshots.borrow_and_update().operational;
+                }
+                message = rx.recv() => match message {
+                    Some(CargoMessage::Scan(scan)) => self.pending.push_back(scan),
+                    Some(CargoMessage::Flush(done)) => {
+                        self.operational = snapshots.borrow_and_update().operational;
+                        self.report_scans().await;
+                        let _ = done.send(());
+                        continue;
+                    }
+                    None => return,
+                },
+            }
+
+            self.report_scans().await;
+        }
+    }
+
+    /// Report parcel scans until svc-cargo fails
+    ///
+    /// Out of service aircraft keep their scans until they return.
+    async fn report_scans(&mut self) {
+        if !self.operational {
+            return;
+        }
+
+        while let Some(scan) = self.pending.front() {
+            if !self.backoff.ready() {
+                return;
+            }
+
+            let cargo_scan = scan.to_cargo_scan(&self.scanner_id);
+            let request = self.scanner.parcel_scan(&self.identifier, cargo_scan);
+            let result = request.await;
+            metrics()
+                .parcel_scans
+                .with_label_values(&[result_label(&result)])
+                .inc();
+
+            match result {
+                Ok(_) => {
+                    self.backoff.success();
+                    self.pending.pop_front();
+                }
+                Err(e) if e.is_transient() => {
+                    let retry_in = self.backoff.failure().as_millis() as u64;
+                    tracing::warn!(cargo_id = %scan.cargo_id, error = %e, retry_in_ms = retry_in, "could not scan parcel");
+                    return;
+                }
+                Err(e) => {
+                    // retrying will not help
+                    tracing::error!(cargo_id = %scan.cargo_id, error = %e, "dropping parcel scan");
+                    self.pending.pop_front();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Network I/O of an aircraft, one task per service
+//!
+//! The physics loop of an [`Aircraft`](crate::Aircraft) never waits on the
+//!  network. Every tick it publishes a [`Snapshot`], queues the telemetry
+//!  frames and parcel scans that are due, and takes the flight plans the
+//!  order link received. Each link has its own queue and backoff, so a slow
+//!  or unavailable service only delays its own traffic. Calls time out in
+//!  [`Guarded`](crate::backend::Guarded), where the timeouts count against
+//!  the circuit breakers.
+
+use serde::Deserialize;
+use svc_atc_client_rest::types::FlightPlan;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::Instrument;
+
+use crate::backend::{CargoScanner, OrderSource, TelemetrySink};
+use crate::retry::RetryPolicy;
+use crate::scenario::Rates;
+
+pub mod cargo;
+pub mod orders;
+pub mod telemetry;
+
+pub use cargo::{CargoLink, CargoMessage};
+pub use orders::{OrderLink, OrderMessage};
+pub use telemetry::{TelemetryLink, TelemetryMessage, Token};
+
+/// Timeouts and queue sizes of the links
+///
+/// The timeouts are enforced by [`Guarded`](crate::backend::Guarded).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct LinkOptions {
+    /// timeout of a login or a NETRID post
+    pub telemetry_timeout_ms: u64,
+    /// timeout of an order poll or acknowledgement
+    pub orders_timeout_ms: u64,
+    /// timeout of a parcel scan
+    pub cargo_timeout_ms: u64,
+    /// frames waiting to be sent; frames are dropped while it is full
+    pub telemetry_queue: usize,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        LinkOptions {
+            telemetry_timeout_ms: 2000,
+            orders_timeout_ms: 10_000,
+            cargo_timeout_ms: 10_000,
+            telemetry_queue: 64,
+        }
+    }
+}
+
+/// State of the aircraft the links need, published every tick
+#[derive(Debug, Clone, Copy)]
+pub struct Snapshot {
+    /// simulation time in ms
+    pub now_ms: u64,
+    /// out of service aircraft do not poll for orders
+    pub operational: bool,
+    /// aircraft shutting down do not poll for orders either, but still
+    ///  answer the plans they have
+    pub accepting_orders: bool,
+}
+
+/// Everything the links of an aircraft are configured with
+#[derive(Debug)]
+pub struct LinkSettings {
+    pub identifier: String,
+    pub uuid: String,
+    pub scanner_id: String,
+    pub rates: Rates,
+    pub retry: RetryPolicy,
+    pub options: LinkOptions,
+    /// flight plans pushed to the aircraft, and the poll interval while
+    ///  the feed is open
+    pub order_feed: Option<(mpsc::Receiver<FlightPlan>, u64)>,
+}
+
+/// Ends of the link channels held by the physics loop
+pub struct Links {
+    pub telemetry: mpsc::Sender<TelemetryMessage>,
+    pub orders: mpsc::UnboundedSender<OrderMessage>,
+    pub cargo: mpsc::UnboundedSender<CargoMessage>,
+    /// flight plans received by the order link
+    pub plans: mpsc::UnboundedReceiver<FlightPlan>,
+    pub snapshots: watch::Sender<Snapshot>,
+}
+
+impl Links {
+    /// Spawn one task per link, in the current span
+    pub fn spawn<T, O, C>(
+        settings: LinkSettings,
+        telemetry: T,
+        orders: O,
+        cargo: C,
+        snapshot: Snapshot,
+    ) -> Self
+    where
+        T: TelemetrySink,
+        O: OrderSource,
+        C: CargoScanner,
+    {
+        let span = tracing::Span::current();
+        let (snapshots, snapshot_rx) = watch::channel(snapshot);
+        let (plans_tx, plans) = mpsc::unbounded_channel();
+
+        let (telemetry_tx, telemetry_rx) = mpsc::channel(settings.options.telemetry_queue.max(1));
+        let link = TelemetryLink::new(telemetry, &settings);
+        tokio::spawn(link.run(telemetry_rx).instrument(span.clone()));
+
+        let (orders_tx, orders_rx) = mpsc::unbounded_channel();
+        let (cargo_tx, cargo_rx) = mpsc::unbounded_channel();
+        let link = CargoLink::new(cargo, &settings);
+        tokio::spawn(link.run(snapshot_rx.clone(), cargo_rx).instrument(span.clone()));
+
+        let link = OrderLink::new(orders, settings, plans_tx);
+        tokio::spawn(link.run(snapshot_rx, orders_rx).instrument(span));
+
+        Links {
+            telemetry: telemetry_tx,
+            orders: orders_tx,
+            cargo: cargo_tx,
+            plans,
+            snapshots,
+        }
+    }
+
+    /// Wait until every link handled what was queued before
+    ///
+    /// Acknowledgements and parcel scans that are waiting for a backoff to
+    ///  elapse stay queued; this does not wait for them.
+    pub async fn flush(&self) {
+        let (done, telemetry) = oneshot::channel();
+        if self.telemetry.send(TelemetryMessage::Flush(done)).await.is_ok() {
+            let _ = telemetry.await;
+        }
+
+        let (done, orders) = oneshot::channel();
+        if self.orders.send(OrderMessage::Flush(done)).is_ok() {
+            let _ = orders.await;
+        }
+
+        let (done, cargo) = oneshot::channel();
+        if self.cargo.send(CargoMessage::Flush(done)).is_ok() {
+            let _ = cargo.await;
+        }
+    }
+}
+
@@ -0,0 +1,241 @@
+//! Order link: flight plan polls, the pushed feed and answers to plans
+
+use std::collections::VecDeque;
+use svc_atc_client_rest::types::FlightPlan;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use super::{LinkSettings, Snapshot};
+use crate::backend::OrderSource;
+use crate::metrics::{metrics, result_label};
+use crate::retry::Backoff;
+
+/// Message from the physics loop to the order link
+#[derive(Debug)]
+pub enum OrderMessage {
+    /// the aircraft accepted the flight plan with this flight id
+    Acknowledge(String),
+    /// the aircraft will not fly the flight plan with this flight id
+    Decline(String),
+    /// answered once the messages queued before it and the latest snapshot
+    ///  were handled
+    Flush(oneshot::Sender<()>),
+}
+
+/// Answer to a flight plan, sent to svc-atc in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Answer {
+    Acknowledge(String),
+    Decline(String),
+}
+
+/// Fetches the flight plans of an aircraft from svc-atc
+///
+/// Polls are due in simulation time, like the rest of the aircraft. Plans
+///  are handed to the physics loop, which sends back the flight ids it
+///  accepted to be acknowledged, or declined on shutdown.
+pub struct OrderLink<O> {
+    source: O,
+    identifier: String,
+    uuid: String,
+    poll_interval_ms: u64,
+    /// flight plans pushed to the aircraft, polling is the fallback
+    feed: Option<mpsc::Receiver<FlightPlan>>,
+    feed_poll_interval_ms: u64,
+    plans: mpsc::UnboundedSender<FlightPlan>,
+    backoff: Backoff,
+    last_poll_ms: u64,
+    /// the last poll failed and is retried after the backoff
+    poll_failed: bool,
+    /// answers not sent yet
+    pending_answers: VecDeque<Answer>,
+}
+
+impl<O: OrderSource> OrderLink<O> {
+    pub fn new(source: O, settings: LinkSettings, plans: mpsc::UnboundedSender<FlightPlan>) -> Self {
+        let (feed, feed_poll_interval_ms) = match settings.order_feed {
+            Some((rx, poll_interval_ms)) => (Some(rx), poll_interval_ms),
+            None => (None, settings.rates.order_poll_interval_ms),
+        };
+
+        OrderLink {
+            source,
+            identifier: settings.identifier,
+            uuid: settings.uuid,
+            poll_interval_ms: settings.rates.order_poll_interval_ms,
+            feed,
+            feed_poll_interval_ms,
+            plans,
+            backoff: Backoff::new(settings.retry),
+            last_poll_ms: 0,
+            poll_failed: false,
+            pending_answers: VecDeque::new(),
+        }
+    }
+
+    /// Handle snapshots, pushed plans and messages until the physics loop
+    ///  is dropped
+    pub async fn run(
+        mut self,
+        mut snapshots: watch::Receiver<Snapshot>,
+        mut rx: mpsc::UnboundedReceiver<OrderMessage>,
+    ) {
+        loop {
+            tokio::select! {
+                changed = snapshots.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+
+                    let snapshot = *snapshots.borrow_and_update();
+                    self.update(snapshot).await;
+                }
+                pushed = recv_feed(&mut self.feed) => match pushed {
+                    Some(plan) => self.forward(vec![plan]),
+                    None => {
+                        tracing::warn!("flight plan feed closed, falling back to polling");
+                        self.feed = None;
+                    }
+                },
+                message = rx.recv() => match message {
+                    Some(OrderMessage::Acknowledge(flight_id)) => {
+                        self.queue_answer(Answer::Acknowledge(flight_id));
+                    }
+                    Some(OrderMessage::Decline(flight_id)) => {
+                        self.queue_answer(Answer::Decline(flight_id));
+                    }
+                    Some(OrderMessage::Flush(done)) => {
+                        let snapshot = *snapshots.borrow_and_update();
+                        self.update(snapshot).await;
+                        let _ = done.send(());
+                    }
+                    None => return,
+                },
+            }
+        }
+    }
+
+    fn queue_answer(&mut self, answer: Answer) {
+        if !self.pending_answers.contains(&answer) {
+            self.pending_answers.push_back(answer);
+        }
+    }
+
+    /// Poll when due and answer plans
+    ///
+    /// Out of service aircraft neither poll nor answer, aircraft shutting
+    ///  down only answer.
+    async fn update(&mut self, snapshot: Snapshot) {
+        if !snapshot.operational {
+            return;
+        }
+
+        if snapshot.accepting_orders {
+            self.poll_orders(snapshot.now_ms).await;
+        }
+        self.answer_orders().await;
+    }
+
+    /// Poll svc-atc for orders when due, or when the last poll failed and
+    ///  the backoff elapsed
+    async fn poll_orders(&mut self, now_ms: u64) {
+        // Every 15000ms by default
+        let poll_interval_ms = match self.feed {
+            Some(_) => self.feed_poll_interval_ms,
+            None => self.poll_interval_ms,
+        };
+
+        let due = now_ms.saturating_sub(self.last_poll_ms) > poll_interval_ms;
+        if !due && !self.poll_failed {
+            return;
+        }
+
+        if !self.backoff.ready() {
+            return;
+        }
+
+        let request = self.source.get_orders(&self.uuid, &self.identifier);
+        let result = request.await;
+        self.last_poll_ms = now_ms;
+        metrics()
+            .order_polls
+            .with_label_values(&[result_label(&result)])
+            .inc();
+
+        match result {
+            Ok(orders) => {
+                self.poll_failed = false;
+                self.backoff.success();
+                self.forward(orders);
+            }
+            Err(e) => {
+                self.poll_failed = true;
+                let retry_in = self.backoff.failure().as_millis() as u64;
+                tracing::warn!(error = %e, retry_in_ms = retry_in, "could not get orders");
+            }
+        }
+    }
+
+    /// Hand flight plans to the physics loop
+    fn forward(&self, orders: Vec<FlightPlan>) {
+        for plan in orders {
+            // the aircraft is gone, the task ends with the next message
+            let _ = self.plans.send(plan);
+        }
+    }
+
+    /// Acknowledge accepted orders and decline the others until svc-atc
+    ///  fails
+    async fn answer_orders(&mut self) {
+        while let Some(answer) = self.pending_answers.front() {
+            if !self.backoff.ready() {
+                return;
+            }
+
+            let result = match answer {
+                Answer::Acknowledge(flight_id) => {
+                    let request = self.source.acknowledge_order(flight_id, &self.identifier);
+                    let result = request.await;
+                    metrics()
+                        .acknowledgements
+                        .with_label_values(&[result_label(&result)])
+                        .inc();
+                    result
+                }
+                Answer::Decline(flight_id) => {
+                    let request = self.source.decline_order(flight_id, &self.identifier);
+                    let result = request.await;
+                    metrics()
+                        .declines
+                        .with_label_values(&[result_label(&result)])
+                        .inc();
+                    result
+                }
+            };
+
+            match result {
+                Ok(_) => {
+                    self.backoff.success();
+                    self.pending_answers.pop_front();
+                }
+                Err(e) if e.is_transient() => {
+                    let retry_in = self.backoff.failure().as_millis() as u64;
+                    tracing::warn!(?answer, error = %e, retry_in_ms = retry_in, "could not answer flight plan");
+                    return;
+                }
+                Err(e) => {
+                    // retrying will not help
+                    tracing::error!(?answer, error = %e, "dropping flight plan answer");
+                    self.pending_answers.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Next plan pushed to the feed, pending forever without a feed
+async fn recv_feed(feed: &mut Option<mpsc::Receiver<FlightPlan>>) -> Option<FlightPlan> {
+    match feed {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
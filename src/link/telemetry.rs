@@ -0,0 +1,375 @@
+//! Telemetry link: login, token refresh and NETRID frames
+
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{LinkSettings};
+use crate::backend::TelemetrySink;
+use crate::error::BackendError;
+use crate::metrics::{metrics, result_label};
+use crate::retry::Backoff;
+use crate::telemetry::TelemetryFrame;
+
+/// Network token issued by svc-telemetry
+///
+/// Lifetimes are in wall clock time, they do not speed up with the
+///  simulation clock.
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub value: String,
+    /// a new token is requested from this point on
+    pub refresh_at: Instant,
+    /// the token is no longer used from this point on
+    pub expires_at: Instant,
+}
+
+/// Share of the lifetime of a token after which it is refreshed
+const TOKEN_REFRESH_AT: f64 = 0.9;
+
+impl Token {
+    /// A token acquired now, valid for `ttl_ms`
+    pub fn new(value: String, ttl_ms: u64) -> Self {
+        let acquired = Instant::now();
+        let ttl = Duration::from_millis(ttl_ms);
+        Token {
+            value,
+            refresh_at: acquired + ttl.mul_f64(TOKEN_REFRESH_AT),
+            expires_at: acquired + ttl,
+        }
+    }
+
+    pub fn due_for_refresh(&self) -> bool {
+        Instant::now() >= self.refresh_at
+    }
+
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+}
+
+/// Message from the physics loop to the telemetry link
+#[derive(Debug)]
+pub enum TelemetryMessage {
+    /// a frame that is due
+    Frame(TelemetryFrame),
+    /// forget the token, e.g. after the aircraft returned to service
+    Relogin,
+    /// answered once the messages queued before it were handled
+    Flush(oneshot::Sender<()>),
+}
+
+/// Sends the NETRID frames of an aircraft to svc-telemetry
+///
+/// Frames are sent in the order they were queued. While svc-telemetry is
+///  unavailable the link backs off and drops the frames that arrive in the
+///  meantime; they would be stale by the time it recovers. Logins back off
+///  on their own, a failed refresh does not hold back the frames while the
+///  current token is still valid.
+pub struct TelemetryLink<T> {
+    sink: T,
+    identifier: String,
+    token_ttl_ms: u64,
+    token: Option<Token>,
+    /// gates the frames
+    backoff: Backoff,
+    /// gates the logins
+    login_backoff: Backoff,
+}
+
+impl<T: TelemetrySink> TelemetryLink<T> {
+    pub fn new(sink: T, settings: &LinkSettings) -> Self {
+        TelemetryLink {
+            sink,
+            identifier: settings.identifier.clone(),
+            token_ttl_ms: settings.rates.token_ttl_ms,
+            token: None,
+            backoff: Backoff::new(settings.retry),
+            login_backoff: Backoff::new(settings.retry),
+        }
+    }
+
+    /// Handle messages until the physics loop is dropped
+    pub async fn run(mut self, mut rx: mpsc::Receiver<TelemetryMessage>) {
+        while let Some(message) = rx.recv().await {
+            match message {
+                TelemetryMessage::Frame(frame) => self.send(frame).await,
+                TelemetryMessage::Relogin => self.token = None,
+                TelemetryMessage::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// Send a frame, logging in first if needed
+    async fn send(&mut self, frame: TelemetryFrame) {
+        if !self.backoff.ready() {
+            drop_frame(&frame);
+            return;
+        }
+
+        let due_for_login = match self.token {
+            Some(ref token) => token.due_for_refresh(),
+            None => true,
+        };
+
+        if due_for_login && self.login_backoff.ready() {
+            self.login().await;
+        }
+
+        // a token due for refresh is used until it actually expires
+        let Some(token) = self
+            .token
+            .as_ref()
+            .filter(|token| !token.expired())
+            .map(|token| token.value.clone())
+        else {
+            drop_frame(&frame);
+            return;
+        };
+
+        let request = self.sink.send_frame(&token, &frame);
+        let result = request.await;
+        let message_type = format!("{:?}", frame.kind);
+        metrics()
+            .telemetry_posts
+            .with_label_values(&[message_type.as_str(), result_label(&result)])
+            .inc();
+
+        match result {
+            Ok(_) => self.backoff.success(),
+            Err(e) => self.failed(e, &message_type),
+        }
+    }
+
+    /// Acquire a new token
+    ///
+    /// On failure the current token, if any, is kept until it expires.
+    async fn login(&mut self) {
+        let request = self.sink.acquire_token(&self.identifier);
+        let result = request.await;
+        metrics()
+            .token_acquisitions
+            .with_label_values(&[result_label(&result)])
+            .inc();
+
+        match result {
+            Ok(value) => {
+                self.login_backoff.success();
+                self.token = Some(Token::new(value, self.token_ttl_ms));
+            }
+            Err(e) => {
+                let retry_in = self.login_backoff.failure().as_millis() as u64;
+                tracing::warn!(
+                    error = %e,
+                    failures = self.login_backoff.failures(),
+                    retry_in_ms = retry_in,
+                    "could not acquire token, flying offline"
+                );
+            }
+        }
+    }
+
+    /// Back off, and log in again if svc-telemetry rejected the token
+    fn failed(&mut self, e: BackendError, message_type: &str) {
+        // a token svc-telemetry keeps rejecting never resets the backoff
+        let retry_in = self.backoff.failure().as_millis() as u64;
+        if e.is_unauthorized() {
+            tracing::warn!(
+                error = %e,
+                message_type,
+                failures = self.backoff.failures(),
+                retry_in_ms = retry_in,
+                "token rejected, logging in again"
+            );
+            self.token = None;
+            return;
+        }
+
+        tracing::warn!(
+            error = %e,
+            message_type,
+            failures = self.backoff.failures(),
+            retry_in_ms = retry_in,
+            "could not issue update, backing off"
+        );
+    }
+}
+
+/// Count a frame that was not sent
+pub(crate) fn drop_frame(frame: &TelemetryFrame) {
+    metrics()
+        .frames_dropped
+        .with_label_values(&[format!("{:?}", frame.kind).as_str()])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use hyper::StatusCode;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use crate::link::LinkOptions;
+    use crate::retry::RetryPolicy;
+    use crate::scenario::Rates;
+    use crate::telemetry::FrameKind;
+
+    /// Answers with the queued results and records the calls, succeeding
+    ///  once the queue is empty
+    #[derive(Clone, Default)]
+    struct Sink {
+        logins: Arc<Mutex<VecDeque<Result<String, BackendError>>>>,
+        posts: Arc<Mutex<VecDeque<Result<(), BackendError>>>>,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TelemetrySink for Sink {
+        async fn acquire_token(&self, _identifier: &str) -> Result<String, BackendError> {
+            self.calls.lock().unwrap().push("login".to_string());
+            let next = self.logins.lock().unwrap().pop_front();
+            next.unwrap_or_else(|| Ok("fresh".to_string()))
+        }
+
+        async fn send_frame(
+            &self,
+            token: &str,
+            _frame: &TelemetryFrame,
+        ) -> Result<(), BackendError> {
+            self.calls.lock().unwrap().push(format!("post {token}"));
+            self.posts.lock().unwrap().pop_front().unwrap_or(Ok(()))
+        }
+    }
+
+    impl Sink {
+        fn calls(&self) -> Vec<String> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+    }
+
+    fn link(sink: &Sink, initial_ms: u64) -> TelemetryLink<Sink> {
+        let settings = LinkSettings {
+            identifier: "Mantis".to_string(),
+            uuid: "uuid".to_string(),
+            scanner_id: "scanner".to_string(),
+            rates: Rates::default(),
+            retry: RetryPolicy {
+                initial_ms,
+                jitter: 0.0,
+                ..Default::default()
+            },
+            options: LinkOptions::default(),
+            order_feed: None,
+        };
+        TelemetryLink::new(sink.clone(), &settings)
+    }
+
+    fn frame() -> TelemetryFrame {
+        TelemetryFrame {
+            identifier: "Mantis".to_string(),
+            kind: FrameKind::Location,
+            timestamp: Utc::now(),
+            payload: vec![0; 25],
+        }
+    }
+
+    /// A token issued earlier, due for refresh, and expired or not
+    fn aged_token(expired: bool) -> Token {
+        let now = Instant::now();
+        Token {
+            value: "aged".to_string(),
+            refresh_at: now,
+            expires_at: if expired { now } else { now + Duration::from_secs(60) },
+        }
+    }
+
+    fn unavailable() -> BackendError {
+        BackendError::from_status("login", StatusCode::SERVICE_UNAVAILABLE)
+    }
+
+    #[tokio::test]
+    async fn logs_in_once_for_many_frames() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 60_000);
+        link.send(frame()).await;
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login", "post fresh", "post fresh"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_sending_with_the_current_token_when_a_refresh_fails() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 60_000);
+        link.token = Some(aged_token(false));
+        sink.logins.lock().unwrap().push_back(Err(unavailable()));
+
+        link.send(frame()).await;
+        link.send(frame()).await;
+
+        // the second refresh waits for the login backoff
+        assert_eq!(sink.calls(), ["login", "post aged", "post aged"]);
+    }
+
+    #[tokio::test]
+    async fn refreshes_a_token_before_it_expires() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 60_000);
+        link.token = Some(aged_token(false));
+
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login", "post fresh"]);
+    }
+
+    #[tokio::test]
+    async fn drops_frames_once_the_token_expired() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 60_000);
+        link.token = Some(aged_token(true));
+        sink.logins.lock().unwrap().push_back(Err(unavailable()));
+
+        link.send(frame()).await;
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login"]);
+    }
+
+    #[tokio::test]
+    async fn logs_in_again_after_the_token_was_rejected() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 0);
+        sink.logins.lock().unwrap().push_back(Ok("rejected".to_string()));
+        sink.posts
+            .lock()
+            .unwrap()
+            .push_back(Err(BackendError::from_status("netrid", StatusCode::UNAUTHORIZED)));
+
+        link.send(frame()).await;
+        assert!(link.token.is_none());
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login", "post rejected", "login", "post fresh"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_token_when_the_service_is_unavailable() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 0);
+        sink.posts.lock().unwrap().push_back(Err(unavailable()));
+
+        link.send(frame()).await;
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login", "post fresh", "post fresh"]);
+    }
+
+    #[tokio::test]
+    async fn drops_frames_while_backing_off() {
+        let sink = Sink::default();
+        let mut link = link(&sink, 60_000);
+        sink.posts.lock().unwrap().push_back(Err(unavailable()));
+
+        link.send(frame()).await;
+        link.send(frame()).await;
+        assert_eq!(sink.calls(), ["login", "post fresh"]);
+        assert!(link.token.is_some());
+    }
+}
@@ -0,0 +1,105 @@
+//! Leveled, structured logging
+//!
+//! Every aircraft runs inside an `aircraft` span carrying its `name` and
+//!  `uuid`, and the `session` of its current flight plan, so log lines do
+//!  not repeat the identifier in their message.
+
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+/// Output format of the log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// human readable lines
+    #[default]
+    Text,
+    /// one JSON object per line, for log shipping
+    Json,
+}
+
+/// Install the global subscriber
+///
+/// `filter` uses the `RUST_LOG` syntax, e.g. `info` or
+///  `sim_carrier=debug,hyper=warn`.
+pub fn init(format: LogFormat, filter: &str) {
+    let filter = EnvFilter::try_new(filter).unwrap_or_else(|e| {
+        eprintln!("invalid log filter {filter}: {e}, using info");
+        EnvFilter::new("info")
+    });
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).try_init(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("could not install log subscriber: {e}");
+    }
+}
+
+/// Minimum simulation time between two per-tick messages of an aircraft
+pub const TICK_LOG_INTERVAL_MS: u64 = 1000;
+
+/// Lets a message through at most once per interval of simulation time
+///
+/// Keeps messages emitted on every tick from flooding the output.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval_ms: u64,
+    last_ms: Option<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(interval_ms: u64) -> Self {
+        RateLimiter {
+            interval_ms,
+            last_ms: None,
+        }
+    }
+
+    /// If a message may be emitted at `now_ms`
+    pub fn allow(&mut self, now_ms: u64) -> bool {
+        match self.last_ms {
+            Some(last_ms) if now_ms.saturating_sub(last_ms) < self.interval_ms => false,
+            _ => {
+                self.last_ms = Some(now_ms);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lets_the_first_message_through() {
+        let mut limiter = RateLimiter::new(1000);
+        assert!(limiter.allow(5_000));
+    }
+
+    #[test]
+    fn holds_back_messages_within_the_interval() {
+        let mut limiter = RateLimiter::new(1000);
+        assert!(limiter.allow(5_000));
+        assert!(!limiter.allow(5_000));
+        assert!(!limiter.allow(5_999));
+
+        // a clock that went back does not let messages through either
+        assert!(!limiter.allow(4_000));
+    }
+
+    #[test]
+    fn lets_a_message_through_once_the_interval_passed() {
+        let mut limiter = RateLimiter::new(1000);
+        assert!(limiter.allow(5_000));
+        assert!(limiter.allow(6_000));
+
+        // the interval starts again from the message let through
+        assert!(!limiter.allow(6_500));
+        assert!(limiter.allow(7_000));
+    }
+}
@@ -1,268 +1,642 @@
 use clap::Parser;
-use hyper::{
-    client::connect::HttpConnector,
-    client::Client,
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use sim_carrier::backend::http::{self, Endpoints, HttpBackend};
+use sim_carrier::backend::{AmqpTelemetry, Guarded, TelemetryOutput};
+use sim_carrier::clock::SimClock;
+use sim_carrier::fleet::FleetContext;
+use sim_carrier::logging::{self, LogFormat};
+use sim_carrier::profile::PerformanceProfile;
+use sim_carrier::retry::Breakers;
+use sim_carrier::scenario::{
+    self, ControlOptions, OutputMode, OutputOptions, Scenario, ScenarioError, SimOptions,
 };
-use svc_telemetry_client_rest::netrid_types::*;
-use svc_atc_client_rest::types::*;
-
-mod orders;
-mod parcel;
-mod telemetry;
-
-use telemetry::*;
-use orders::*;
-
-/// Simple program to greet a person
+use sim_carrier::shutdown::{self, ShutdownMode};
+use sim_carrier::wind::WindModel;
+use sim_carrier::{batch, checkpoint, control, fleet, uas_id, AircraftConfig};
+
+/// Simulates carrier aircraft against the telemetry, atc and cargo services
+///
+/// Values from the scenario file are overridden by environment variables,
+///  which are in turn overridden by command line flags.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Name of the person to greet
-    // #[arg(long)]
-    // host: String,
+    /// scenario file (JSON) with services, rates, sim options and aircraft
+    #[arg(long, alias = "fleet", env = "SIM_SCENARIO")]
+    scenario: Option<String>,
+
+    /// host of the backend services
+    #[arg(long, env = "SIM_HOST")]
+    host: Option<String>,
 
     /// telemetry service port
-    #[arg(long)]
-    tlm_port: u16,
+    #[arg(long, env = "SIM_TLM_PORT")]
+    tlm_port: Option<u16>,
 
     /// atc service port
-    #[arg(long)]
-    atc_port: u16,
+    #[arg(long, env = "SIM_ATC_PORT")]
+    atc_port: Option<u16>,
 
     /// cargo service port
-    #[arg(long)]
-    cargo_port: u16,
+    #[arg(long, env = "SIM_CARGO_PORT")]
+    cargo_port: Option<u16>,
+
+    /// reach the backend services over https
+    #[arg(long, env = "SIM_HTTPS")]
+    https: bool,
+
+    /// PEM file with the CA certificates trusted for https
+    #[arg(long, env = "SIM_CA_BUNDLE")]
+    ca_bundle: Option<String>,
+
+    /// speak HTTP/2 to the backend services without negotiating it
+    #[arg(long, env = "SIM_HTTP2_PRIOR_KNOWLEDGE")]
+    http2_prior_knowledge: bool,
+
+    /// timeout of connecting to a backend service in milliseconds
+    #[arg(long, env = "SIM_CONNECT_TIMEOUT_MS")]
+    connect_timeout_ms: Option<u64>,
+
+    /// timeout of a backend request in milliseconds
+    #[arg(long, env = "SIM_REQUEST_TIMEOUT_MS")]
+    request_timeout_ms: Option<u64>,
+
+    /// simulation tick in milliseconds
+    #[arg(long, env = "SIM_TICK_MS")]
+    tick_ms: Option<u64>,
+
+    /// simulation time speed-up factor, e.g. 10 runs ten times faster than real time
+    #[arg(long, env = "SIM_SPEED")]
+    speed: Option<f64>,
+
+    /// step the clock by the tick as fast as possible, with no real time sleeps
+    #[arg(long, env = "SIM_FAST_FORWARD")]
+    fast_forward: bool,
+
+    /// where telemetry frames are sent
+    #[arg(long, value_enum, env = "SIM_TELEMETRY_OUTPUT")]
+    telemetry_output: Option<OutputMode>,
+
+    /// AMQP broker uri for telemetry output
+    #[arg(long, env = "SIM_AMQP_URI")]
+    amqp_uri: Option<String>,
+
+    /// AMQP exchange for telemetry output
+    #[arg(long, env = "SIM_AMQP_EXCHANGE")]
+    amqp_exchange: Option<String>,
+
+    /// AMQP broker uri to subscribe to pushed flight plans, polling remains the fallback
+    #[arg(long, env = "SIM_ORDERS_AMQP_URI")]
+    orders_amqp_uri: Option<String>,
+
+    /// serve the control and inspection API on this port
+    #[arg(long, env = "SIM_CONTROL_PORT")]
+    control_port: Option<u16>,
+
+    /// how aircraft flying a plan get to the ground on SIGINT or SIGTERM
+    #[arg(long, value_enum, env = "SIM_SHUTDOWN_MODE")]
+    shutdown_mode: Option<ShutdownMode>,
+
+    /// decline queued flight plans with svc-atc on SIGINT or SIGTERM
+    #[arg(long, env = "SIM_DECLINE_QUEUED")]
+    decline_queued: bool,
+
+    /// periodically save the state of every aircraft to this file (JSON)
+    #[arg(long, env = "SIM_CHECKPOINT")]
+    checkpoint: Option<String>,
+
+    /// restore the aircraft from the checkpoint file, if it exists
+    #[arg(long, env = "SIM_RESUME")]
+    resume: bool,
+
+    /// flight plans file (JSON); fly each plan headless without a backend,
+    ///  print a feasibility report and exit
+    #[arg(long, conflicts_with = "name")]
+    batch: Option<String>,
+
+    /// performance profile of the scenario file for the aircraft without
+    ///  one, and for the plans of a batch
+    #[arg(long, env = "SIM_PROFILE")]
+    profile: Option<String>,
 
-    /// aircraft name
-    #[arg(long)]
-    name: String,
+    /// aircraft name, replaces the aircraft of the scenario file
+    #[arg(long, env = "SIM_NAME", requires_all = ["uuid", "longitude", "latitude", "scanner_id"])]
+    name: Option<String>,
 
     /// aircraft uuid
-    #[arg(long)]
-    uuid: String,
+    #[arg(long, env = "SIM_UUID", requires = "name")]
+    uuid: Option<String>,
 
     /// starting longitude
-    #[arg(long)]
-    longitude: f64,
+    #[arg(long, env = "SIM_LONGITUDE", requires = "name")]
+    longitude: Option<f64>,
 
     /// starting latitude
-    #[arg(long)]
-    latitude: f64,
+    #[arg(long, env = "SIM_LATITUDE", requires = "name")]
+    latitude: Option<f64>,
 
     /// scanner id
-    #[arg(long)]
-    scanner_id: String
-}
+    #[arg(long, env = "SIM_SCANNER_ID", requires = "name")]
+    scanner_id: Option<String>,
 
-pub enum Activity {
-    Idle,
-    Cruise,
-}
-const SLEEP_TIME_MS: u64 = 50;
-
-struct State {
-    current_plan: Option<FlightPlan>,
-    id: String,
-    scanner_id: String,
-    token: Option<String>,
-    activity: Activity,
-    position: PointZ,
-    ground_velocity_m_s: f64,
-    vertical_velocity_m_s: f64,
-    track_angle_deg: f64,
-    last_update_ms: u64,
-    last_id_update_ms: u64,
-    last_order_check: u64,
-    // operational: bool, // for simulating sudden out of service
+    /// ANSI/CTA-2063-A serial number, sent as the UAS ID while no flight plan is active
+    #[arg(long, env = "SIM_SERIAL_NUMBER", requires = "name")]
+    serial_number: Option<String>,
+
+    /// log line format
+    #[arg(long, value_enum, env = "SIM_LOG_FORMAT", default_value = "text")]
+    log_format: LogFormat,
+
+    /// log filter, e.g. `info` or `sim_carrier=debug,hyper=warn`
+    #[arg(long, env = "RUST_LOG", default_value = "info")]
+    log_level: String,
 }
 
 #[tokio::main]
 async fn main() {
-
     let args = Args::parse();
-    let identifier = args.name;
-    println!("({}) aircraft startup.", identifier);
-
-    let scanner_id = args.scanner_id.replace('"', "");
-    let tlm_uri = format!("http://0.0.0.0:{}/telemetry", args.tlm_port);
-    let atc_uri = format!("http://0.0.0.0:{}/atc", args.atc_port);
-    let cargo_uri = format!("http://0.0.0.0:{}/cargo", args.cargo_port);
-
-    let client: Client<HttpConnector> = Client::builder()
-        .pool_idle_timeout(std::time::Duration::from_secs(10))
-        .build_http();
-
-    let mut state = State {
-        id: identifier.clone(),
-        scanner_id,
-        current_plan: None,
-        activity: Activity::Idle,
-        token: None,
-        position: PointZ {
-            longitude: args.longitude,
-            latitude: args.latitude,
-            altitude_meters: 0.0,
+    logging::init(args.log_format, &args.log_level);
+
+    let mut scenario = match args.scenario {
+        Some(ref path) => match scenario::load(path) {
+            Ok(scenario) => scenario,
+            Err(e) => {
+                tracing::error!(%path, error = %e, "could not load scenario");
+                std::process::exit(1);
+            }
         },
-        ground_velocity_m_s: 0.0,
-        vertical_velocity_m_s: 0.0,
-        track_angle_deg: 0.0,
-        last_update_ms: 0,
-        last_id_update_ms: 0,
-        last_order_check: 0,
-        // operational: true,
+        None => Scenario::default(),
     };
 
-    let mut plans: Vec<FlightPlan> = vec![];
-    let mut retry = 0;
-    let max_retries = 5;
-    let retry_interval = tokio::time::Duration::from_secs(5);
-    let uuid = args.uuid;
-
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(SLEEP_TIME_MS));
-    let mut last_tick = chrono::Utc::now().timestamp_millis() as u64;
-    loop {
-        interval.tick().await;
-        let current_tick = chrono::Utc::now().timestamp_millis() as u64;
-        update_location(&current_tick, &last_tick, &mut state);
-        adjust_vertical_velocity(&current_tick, &mut state);
-        last_tick = current_tick;
-
-        // Check for new orders
-        if state.current_plan.is_none() {
-            let mut activate = false;
-            if let Some(ref fp) = plans.first() {
-                if (fp.origin_timeslot_end.timestamp_millis() as u64) < current_tick {
-                    activate = true;
-                }
+    let batch = args.batch.clone();
+    let batch_profile = args.profile.clone();
+    apply_overrides(&mut scenario, args);
+    let rates = scenario.telemetry;
+    let options = scenario.sim;
+    let output = scenario.output.clone();
+    let order_feed = scenario.orders.amqp.clone();
+    let control_options = scenario.control.clone();
+    let retry = scenario.retry;
+    let links = scenario.links;
+    let http_options = scenario.http.clone();
+    let shutdown_options = scenario.shutdown;
+    let checkpoint_options = scenario.checkpoint.clone();
+    let kinematics = scenario.kinematics;
+    let phases = scenario.phases;
+    let battery = scenario.battery;
+    let profiles = scenario.profiles.clone();
+    let wind = match WindModel::load(&scenario.wind) {
+        Ok(wind) => wind,
+        Err(e) => {
+            tracing::error!(error = %e, "invalid scenario");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(path) = batch {
+        if let Err(e) = check_tick(&options) {
+            tracing::error!(error = %e, "invalid scenario");
+            std::process::exit(1);
+        }
+
+        let profile = match find_profile(&profiles, "batch", batch_profile.as_deref()) {
+            Ok(profile) => profile,
+            Err(e) => {
+                tracing::error!(error = %e, "invalid scenario");
+                std::process::exit(1);
             }
+        };
 
-            if activate {
-                let plan = plans.remove(0);
-                orders::init_plan(&client, &mut state, &cargo_uri, current_tick, plan).await;
+        let battery = battery.as_ref();
+        match batch::run(&path, options.tick_ms, &kinematics, &phases, &profile, battery, &wind) {
+            Ok(0) => return,
+            Ok(_) => std::process::exit(2),
+            Err(e) => {
+                tracing::error!(%path, error = %e, "could not run batch");
+                std::process::exit(1);
             }
         }
+    }
 
-        if let Some(ref plan) = state.current_plan {
-            if plan.path.is_empty() {
-                orders::end_plan(&client, &mut state, &cargo_uri).await;
-            }
+    let (endpoints, aircraft) = match resolve(scenario) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            tracing::error!(error = %e, "invalid scenario");
+            std::process::exit(1);
         }
+    };
 
-        // Acquire network token if not present
-        let Some(ref token) = state.token else {
-            if let Ok(token) = acquire_token(&client, &tlm_uri, state.id.clone()).await {
-                state.token = Some(token);
-                retry = 0;
-
-                continue;
-            } else {
-                retry += 1;
-                if retry > max_retries {
-                    panic!(
-                        "({}) could not acquire token, expeded all retries.",
-                        state.id
-                    );
-                }
+    let clock = if options.fast_forward {
+        SimClock::stepped(chrono::Utc::now(), options.tick_ms)
+    } else {
+        SimClock::new(options.speed)
+    };
 
-                tokio::time::sleep(retry_interval).await;
-                continue;
-            }
-        };
+    let client = match http::client(&http_options) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(error = %e, "could not build HTTP client");
+            std::process::exit(1);
+        }
+    };
 
-        // Every 2000ms (0.5 Hz)
-        if current_tick - state.last_id_update_ms > 2000 {
-            let (id_type, id) = match state.current_plan {
-                Some(ref p) => (IdType::SpecificSession, p.session_id.clone()),
-                None => (IdType::CaaAssigned, state.id.clone())
-            };
-
-            // issue id update
-            let result = id_update(
-                &client,
-                &tlm_uri,
-                id_type,
-                &id,
-                &token
-            ).await;
-
-            match result {
-                Ok(_) => {
-                    state.last_id_update_ms = current_tick;
-                }
+    // shared by the whole fleet, one breaker per endpoint
+    let breakers = Breakers::new(retry);
+    let http = HttpBackend::new(client, endpoints)
+        .with_request_timeout(Duration::from_millis(http_options.request_timeout_ms));
+    let backend = Guarded::new(http.clone(), breakers.clone()).with_timeouts(links);
+    let telemetry = match telemetry_output(output, &http).await {
+        Ok(telemetry) => Guarded::new(telemetry, breakers).with_timeouts(links),
+        Err(e) => {
+            tracing::error!(error = %e, "could not connect to broker");
+            std::process::exit(1);
+        }
+    };
+
+    let mut context = FleetContext::new(clock, rates, options, order_feed, retry, links)
+        .with_kinematic_limits(kinematics)
+        .with_phase_options(phases)
+        .with_profiles(profiles)
+        .with_wind(wind)
+        .with_shutdown(shutdown::listen(shutdown_options));
+    if let Some(battery) = battery {
+        context = context.with_battery(battery);
+    }
+    if let Some(ref path) = checkpoint_options.path {
+        if checkpoint_options.resume {
+            match checkpoint::load_for_resume(path) {
+                Ok(Some(saved)) => context = context.with_resume(saved),
+                Ok(None) => tracing::info!(%path, "no checkpoint to resume from, starting fresh"),
                 Err(e) => {
-                    println!("({}) could not issue id update: {}", state.id, e);
-                    state.token = None;
-                    continue;
+                    tracing::error!(error = %e, "could not resume");
+                    std::process::exit(1);
                 }
             }
         }
 
-        // Every 500ms (2 Hz)
-        if current_tick - state.last_update_ms > 500 {
-            // issue position and velocity update
-            let result = position_update(&client, &tlm_uri, &token, &state).await;
+        let interval_ms = checkpoint_options.interval_ms;
+        tokio::spawn(checkpoint::run(path.clone(), interval_ms, context.status.clone()));
+    }
 
-            match result {
-                Ok(_) => {
-                    state.last_update_ms = current_tick;
-                }
-                Err(e) => {
-                    println!("({}) could not issue position update: {}", state.id, e);
-                    state.token = None;
-                    continue;
-                }
+    if let Some(addr) = control_addr(&control_options) {
+        let status = context.status.clone();
+        let controls = context.controls.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(addr, status, controls).await {
+                tracing::error!(error = %e, "control API server error");
             }
+        });
+    }
+
+    let status = context.status.clone();
+    let reports = if aircraft.len() > 1 {
+        fleet::run(context, aircraft, telemetry, backend.clone(), backend).await
+    } else {
+        let Some(config) = aircraft.into_iter().next() else {
+            return;
+        };
+
+        let aircraft = context
+            .aircraft(config, telemetry, backend.clone(), backend)
+            .await;
+
+        vec![aircraft.run(options.tick_ms, status.clone()).await]
+    };
+
+    shutdown::summarize(&reports);
+
+    // the next start resumes from the landed fleet
+    if let Some(ref path) = checkpoint_options.path {
+        if let Err(e) = checkpoint::save(path, &status) {
+            tracing::error!(error = %e, "could not write final checkpoint");
         }
+    }
+}
 
-        // Every 15000ms
-        if current_tick - state.last_order_check > 15000 {
-            // issue position and velocity update
-            let result = get_orders(&client, &atc_uri, uuid.clone(), &identifier).await;
-            state.last_order_check = current_tick;
-
-            match result {
-                Ok(orders) => {
-                    for order in orders {
-                        let mut in_place = false;
-                        plans.iter_mut().for_each(|p| if p.session_id == order.session_id {
-                            *p = order.clone();
-                            in_place = true;
-                        });
-
-                        if !in_place {
-                            plans.push(order.clone());
-                        }
-
-                        // acknowledge order
-                        let _ = acknowledge_order(&client, &atc_uri, &order.flight_uuid, &identifier).await;
-                    }
-                }
-                Err(e) => {
-                    println!("({}) could not get orders: {}", state.id, e);
-                    continue;
-                }
-            }
+/// Address of the control API, if enabled
+fn control_addr(options: &ControlOptions) -> Option<SocketAddr> {
+    let port = options.port?;
+    match format!("{}:{}", options.host, port).parse() {
+        Ok(addr) => Some(addr),
+        Err(e) => {
+            tracing::error!(host = %options.host, port, error = %e, "invalid control API address");
+            None
         }
+    }
+}
 
-        // Every 10s (0.1 Hz)
-        // if current_tick - state.last_order_check > 10000 {
-        //     // check for orders
-        //     state.last_order_check = current_tick;
-        // }
-
-        match state.activity {
-            Activity::Idle => {
-                if state.current_plan.is_some() {
-                    state.activity = Activity::Cruise;
-                    continue;
-                }
-            }
-            Activity::Cruise => {
-                // if current position is within 1 meters of destination
-                // switch to Idle
-                // state.current_plan = None;
-                // state.activity = Activity::Idle;
-            }
+/// Build the telemetry sink for the configured output mode
+async fn telemetry_output(
+    output: OutputOptions,
+    backend: &HttpBackend,
+) -> Result<TelemetryOutput, lapin::Error> {
+    let telemetry = match output.mode {
+        OutputMode::Http => TelemetryOutput::Http(backend.clone()),
+        OutputMode::Amqp => TelemetryOutput::Amqp(AmqpTelemetry::connect(output.amqp).await?),
+        OutputMode::Both => TelemetryOutput::Both(
+            backend.clone(),
+            AmqpTelemetry::connect(output.amqp).await?,
+        ),
+    };
+
+    Ok(telemetry)
+}
+
+/// Apply command line flags and environment variables on top of the scenario file
+fn apply_overrides(scenario: &mut Scenario, args: Args) {
+    if let Some(host) = args.host {
+        scenario.services.host = host;
+    }
+
+    if args.tlm_port.is_some() {
+        scenario.services.tlm_port = args.tlm_port;
+    }
+
+    if args.atc_port.is_some() {
+        scenario.services.atc_port = args.atc_port;
+    }
+
+    if args.cargo_port.is_some() {
+        scenario.services.cargo_port = args.cargo_port;
+    }
+
+    if args.https {
+        scenario.http.https = true;
+    }
+
+    if args.ca_bundle.is_some() {
+        scenario.http.ca_bundle = args.ca_bundle;
+    }
+
+    if args.http2_prior_knowledge {
+        scenario.http.http2_prior_knowledge = true;
+    }
+
+    if let Some(timeout_ms) = args.connect_timeout_ms {
+        scenario.http.connect_timeout_ms = timeout_ms;
+    }
+
+    if let Some(timeout_ms) = args.request_timeout_ms {
+        scenario.http.request_timeout_ms = timeout_ms;
+    }
+
+    if let Some(tick_ms) = args.tick_ms {
+        scenario.sim.tick_ms = tick_ms;
+    }
+
+    if let Some(speed) = args.speed {
+        scenario.sim.speed = speed;
+    }
+
+    if args.fast_forward {
+        scenario.sim.fast_forward = true;
+    }
+
+    if let Some(mode) = args.telemetry_output {
+        scenario.output.mode = mode;
+    }
+
+    if let Some(uri) = args.amqp_uri {
+        scenario.output.amqp.uri = uri;
+    }
+
+    if let Some(exchange) = args.amqp_exchange {
+        scenario.output.amqp.exchange = exchange;
+    }
+
+    if let Some(uri) = args.orders_amqp_uri {
+        scenario.orders.amqp.get_or_insert_with(Default::default).uri = uri;
+    }
+
+    if args.control_port.is_some() {
+        scenario.control.port = args.control_port;
+    }
+
+    if let Some(mode) = args.shutdown_mode {
+        scenario.shutdown.mode = mode;
+    }
+
+    if args.decline_queued {
+        scenario.shutdown.decline_queued = true;
+    }
+
+    if args.checkpoint.is_some() {
+        scenario.checkpoint.path = args.checkpoint;
+    }
+
+    if args.resume {
+        scenario.checkpoint.resume = true;
+    }
+
+    // clap guarantees the remaining identity flags are present with the name
+    if let Some(name) = args.name {
+        scenario.aircraft = vec![AircraftConfig {
+            name,
+            uuid: args.uuid.unwrap_or_default(),
+            scanner_id: args.scanner_id.unwrap_or_default(),
+            longitude: args.longitude.unwrap_or_default(),
+            latitude: args.latitude.unwrap_or_default(),
+            serial_number: args.serial_number,
+            profile: None,
+        }];
+    }
+
+    if args.profile.is_some() {
+        for aircraft in scenario.aircraft.iter_mut().filter(|a| a.profile.is_none()) {
+            aircraft.profile = args.profile.clone();
+        }
+    }
+}
+
+/// Performance profile `profile` of the scenario, the default profile
+///  without a name
+fn find_profile(
+    profiles: &HashMap<String, PerformanceProfile>,
+    name: &str,
+    profile: Option<&str>,
+) -> Result<PerformanceProfile, ScenarioError> {
+    let Some(profile) = profile else {
+        return Ok(PerformanceProfile::default());
+    };
+
+    profiles
+        .get(profile)
+        .copied()
+        .ok_or_else(|| ScenarioError::UnknownProfile(name.to_string(), profile.to_string()))
+}
+
+/// The clock never advances with a tick of 0 ms
+fn check_tick(options: &SimOptions) -> Result<(), ScenarioError> {
+    if options.tick_ms == 0 {
+        return Err(ScenarioError::ZeroInterval("sim.tick_ms"));
+    }
+
+    Ok(())
+}
+
+/// Build the service endpoints and aircraft list from a complete scenario
+fn resolve(scenario: Scenario) -> Result<(Endpoints, Vec<AircraftConfig>), ScenarioError> {
+    let services = scenario.services;
+    // no service listens on port 0
+    let port = |port: Option<u16>, service| {
+        port.filter(|port| *port != 0).ok_or(ScenarioError::MissingPort(service))
+    };
+    let tlm_port = port(services.tlm_port, "telemetry")?;
+    let atc_port = port(services.atc_port, "atc")?;
+    let cargo_port = port(services.cargo_port, "cargo")?;
+
+    if scenario.aircraft.is_empty() {
+        return Err(ScenarioError::NoAircraft);
+    }
+
+    if scenario.checkpoint.resume && scenario.checkpoint.path.is_none() {
+        return Err(ScenarioError::NoCheckpoint);
+    }
+
+    if scenario.sim.speed.is_nan() || scenario.sim.speed <= 0.0 {
+        return Err(ScenarioError::InvalidSpeed(scenario.sim.speed));
+    }
+
+    check_tick(&scenario.sim)?;
+    if scenario.sim.status_interval_ms == 0 {
+        return Err(ScenarioError::ZeroInterval("sim.status_interval_ms"));
+    }
+
+    if scenario.checkpoint.interval_ms == 0 {
+        return Err(ScenarioError::ZeroInterval("checkpoint.interval_ms"));
+    }
+
+    for aircraft in &scenario.aircraft {
+        if let Some(ref serial) = aircraft.serial_number {
+            uas_id::validate_serial(serial)
+                .map_err(|e| ScenarioError::InvalidSerial(aircraft.name.clone(), e))?;
         }
+
+        find_profile(&scenario.profiles, &aircraft.name, aircraft.profile.as_deref())?;
+    }
+
+    let scheme = if scenario.http.https { "https" } else { "http" };
+    let endpoints = Endpoints {
+        tlm_uri: format!("{}://{}:{}/telemetry", scheme, services.host, tlm_port),
+        atc_uri: format!("{}://{}:{}/atc", scheme, services.host, atc_port),
+        cargo_uri: format!("{}://{}:{}/cargo", scheme, services.host, cargo_port),
+    };
+
+    Ok((endpoints, scenario.aircraft))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scenario file with every port and a single aircraft
+    const SCENARIO: &str = r#"{
+        "services": { "host": "backend", "tlm_port": 8001, "atc_port": 8002, "cargo_port": 8003 },
+        "sim": { "tick_ms": 100 },
+        "profiles": { "heavy": { "cruise_speed_m_s": 12.0 } },
+        "aircraft": [
+            {
+                "name": "Mantis", "uuid": "uuid", "scanner_id": "scanner",
+                "longitude": 4.9, "latitude": 52.4
+            }
+        ]
+    }"#;
+
+    fn from_file() -> Scenario {
+        serde_json::from_str(SCENARIO).expect("scenario should parse")
+    }
+
+    fn overridden(flags: &[&str]) -> Scenario {
+        let args = Args::try_parse_from(std::iter::once("sim-carrier").chain(flags.iter().copied()))
+            .expect("flags should parse");
+        let mut scenario = from_file();
+        apply_overrides(&mut scenario, args);
+        scenario
+    }
+
+    #[test]
+    fn flags_override_the_scenario_file() {
+        let flags = ["--host", "localhost", "--atc-port", "9002", "--tick-ms", "20"];
+        let scenario = overridden(&flags);
+        assert_eq!(scenario.sim.tick_ms, 20);
+
+        let (endpoints, aircraft) = resolve(scenario).expect("scenario should resolve");
+        assert_eq!(endpoints.tlm_uri, "http://localhost:8001/telemetry");
+        assert_eq!(endpoints.atc_uri, "http://localhost:9002/atc");
+        assert_eq!(aircraft[0].name, "Mantis");
+    }
+
+    #[test]
+    fn the_scenario_file_overrides_the_defaults() {
+        let scenario = overridden(&[]);
+        assert_eq!(scenario.sim.tick_ms, 100);
+        assert_eq!(scenario.sim.speed, SimOptions::default().speed);
+
+        let (endpoints, _) = resolve(scenario).expect("scenario should resolve");
+        assert_eq!(endpoints.cargo_uri, "http://backend:8003/cargo");
+    }
+
+    #[test]
+    fn flags_replace_the_aircraft() {
+        let flags = [
+            "--name", "Hornet", "--uuid", "uuid-2", "--longitude", "4.8", "--latitude", "52.3",
+            "--scanner-id", "scanner-2", "--profile", "heavy",
+        ];
+        let (_, aircraft) = resolve(overridden(&flags)).expect("scenario should resolve");
+        assert_eq!(aircraft.len(), 1);
+        assert_eq!(aircraft[0].name, "Hornet");
+        assert_eq!(aircraft[0].profile.as_deref(), Some("heavy"));
+    }
+
+    #[test]
+    fn rejects_missing_and_zero_ports() {
+        let mut scenario = from_file();
+        scenario.services.atc_port = None;
+        assert!(matches!(resolve(scenario), Err(ScenarioError::MissingPort("atc"))));
+
+        let scenario = overridden(&["--cargo-port", "0"]);
+        assert!(matches!(resolve(scenario), Err(ScenarioError::MissingPort("cargo"))));
+    }
+
+    #[test]
+    fn rejects_zero_intervals() {
+        let scenario = overridden(&["--tick-ms", "0"]);
+        assert!(matches!(resolve(scenario), Err(ScenarioError::ZeroInterval("sim.tick_ms"))));
+
+        let mut scenario = from_file();
+        scenario.sim.status_interval_ms = 0;
+        let error = resolve(scenario);
+        assert!(matches!(error, Err(ScenarioError::ZeroInterval("sim.status_interval_ms"))));
+
+        let mut scenario = from_file();
+        scenario.checkpoint.interval_ms = 0;
+        let error = resolve(scenario);
+        assert!(matches!(error, Err(ScenarioError::ZeroInterval("checkpoint.interval_ms"))));
+    }
+
+    #[test]
+    fn rejects_invalid_serial_numbers() {
+        let mut scenario = from_file();
+        scenario.aircraft[0].serial_number = Some("MFR1".to_string());
+        let error = resolve(scenario);
+        let name = match error {
+            Err(ScenarioError::InvalidSerial(name, _)) => name,
+            other => panic!("expected an invalid serial, got {other:?}"),
+        };
+        assert_eq!(name, "Mantis");
+
+        let mut scenario = from_file();
+        scenario.aircraft[0].serial_number = Some("MFR1C123456789ABC".to_string());
+        assert!(resolve(scenario).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_profiles() {
+        let scenario = overridden(&["--profile", "light"]);
+        let error = resolve(scenario);
+        assert!(matches!(error, Err(ScenarioError::UnknownProfile(_, ref p)) if p == "light"));
     }
 }
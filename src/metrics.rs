@@ -0,0 +1,245 @@
+//! Prometheus metrics of the simulator and the backends it talks to
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::fleet::StatusBoard;
+
+/// `result` label of successful requests
+pub const OK: &str = "ok";
+/// `result` label of failed requests
+pub const ERROR: &str = "error";
+
+/// All metrics of the process
+pub struct Metrics {
+    registry: Registry,
+    /// held while the activity gauges are recomputed and gathered, so
+    ///  concurrent scrapes do not see them half set
+    render: Mutex<()>,
+    /// telemetry frames sent, by `message_type` and `result`
+    pub telemetry_posts: IntCounterVec,
+    /// token acquisitions, by `result`
+    pub token_acquisitions: IntCounterVec,
+    /// order polls, by `result`
+    pub order_polls: IntCounterVec,
+    /// flight plan acknowledgements, by `result`
+    pub acknowledgements: IntCounterVec,
+    /// flight plans declined on shutdown, by `result`
+    pub declines: IntCounterVec,
+    /// parcel scans, by `result`
+    pub parcel_scans: IntCounterVec,
+    /// telemetry frames that could not be built, by `message_type`
+    pub frame_encoding_errors: IntCounterVec,
+    /// telemetry frames dropped while the queue was full or svc-telemetry
+    ///  was unavailable, by `message_type`
+    pub frames_dropped: IntCounterVec,
+    /// HTTP request latency in seconds, by `endpoint`
+    pub http_latency: HistogramVec,
+    /// aircraft per `activity`
+    pub aircraft_activity: IntGaugeVec,
+    /// 1 while the circuit breaker of an `endpoint` is open
+    pub circuit_breaker_open: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("sim_carrier".to_string()), None)
+            .expect("metrics prefix is valid");
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels)
+                .expect("metric definition is valid");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("metric is registered once");
+            counter
+        };
+
+        let telemetry_posts = counter(
+            "telemetry_posts_total",
+            "Telemetry frames sent",
+            &["message_type", "result"],
+        );
+        let token_acquisitions =
+            counter("token_acquisitions_total", "Token acquisitions", &["result"]);
+        let order_polls = counter("order_polls_total", "Order polls", &["result"]);
+        let acknowledgements = counter(
+            "acknowledgements_total",
+            "Flight plan acknowledgements",
+            &["result"],
+        );
+        let declines = counter("declines_total", "Flight plans declined", &["result"]);
+        let parcel_scans = counter("parcel_scans_total", "Parcel scans", &["result"]);
+        let frame_encoding_errors = counter(
+            "frame_encoding_errors_total",
+            "Telemetry frames that could not be built",
+            &["message_type"],
+        );
+        let frames_dropped = counter(
+            "frames_dropped_total",
+            "Telemetry frames dropped before they were sent",
+            &["message_type"],
+        );
+
+        let http_latency = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+            &["endpoint"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(http_latency.clone()))
+            .expect("metric is registered once");
+
+        let aircraft_activity = IntGaugeVec::new(
+            Opts::new("aircraft", "Aircraft per activity"),
+            &["activity"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(aircraft_activity.clone()))
+            .expect("metric is registered once");
+
+        let circuit_breaker_open = IntGaugeVec::new(
+            Opts::new("circuit_breaker_open", "Open circuit breakers"),
+            &["endpoint"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(circuit_breaker_open.clone()))
+            .expect("metric is registered once");
+
+        Metrics {
+            registry,
+            render: Mutex::new(()),
+            telemetry_posts,
+            token_acquisitions,
+            order_polls,
+            acknowledgements,
+            declines,
+            parcel_scans,
+            frame_encoding_errors,
+            frames_dropped,
+            http_latency,
+            aircraft_activity,
+            circuit_breaker_open,
+        }
+    }
+}
+
+/// Metrics of the process, created on first use
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// `result` label for the outcome of a request
+pub fn result_label<T, E>(result: &Result<T, E>) -> &'static str {
+    match result {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
+/// Render all metrics in the Prometheus text format
+///
+/// The activity gauges are computed from `status` at render time.
+pub fn render(status: &StatusBoard) -> Result<String, String> {
+    let metrics = metrics();
+
+    let mut activities: HashMap<String, i64> = HashMap::new();
+    if let Ok(board) = status.lock() {
+        for aircraft in board.values() {
+            *activities.entry(format!("{:?}", aircraft.activity)).or_default() += 1;
+        }
+    }
+
+    // a scrape that panicked left nothing to clean up
+    let _render = metrics.render.lock().unwrap_or_else(|e| e.into_inner());
+    metrics.aircraft_activity.reset();
+    for (activity, count) in activities {
+        metrics
+            .aircraft_activity
+            .with_label_values(&[activity.as_str()])
+            .set(count);
+    }
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|e| e.to_string())?;
+
+    String::from_utf8(buffer).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+    use crate::Activity;
+
+    /// Value of the sample of `metric` whose labels end in `labels`
+    fn sample(text: &str, metric: &str, labels: &str) -> Option<f64> {
+        let prefix = format!("sim_carrier_{metric}{{{labels}}} ");
+        text.lines()
+            .find_map(|line| line.strip_prefix(prefix.as_str()))
+            .map(|value| value.parse().expect("sample value is a number"))
+    }
+
+    fn board(activities: &[Activity]) -> StatusBoard {
+        let status = StatusBoard::default();
+        for (i, activity) in activities.iter().enumerate() {
+            let aircraft = testing::aircraft_status(*activity);
+            status.lock().unwrap().insert(format!("aircraft-{i}"), aircraft);
+        }
+        status
+    }
+
+    #[test]
+    fn counts_what_happened() {
+        let status = board(&[]);
+        let errors = &metrics().frame_encoding_errors;
+        errors.with_label_values(&["metrics-test"]).inc();
+        let before = render(&status).expect("metrics render");
+
+        errors.with_label_values(&["metrics-test"]).inc_by(2);
+        let after = render(&status).expect("metrics render");
+
+        let labels = r#"message_type="metrics-test""#;
+        assert_eq!(sample(&before, "frame_encoding_errors_total", labels), Some(1.0));
+        assert_eq!(sample(&after, "frame_encoding_errors_total", labels), Some(3.0));
+    }
+
+    #[test]
+    fn counts_the_aircraft_per_activity() {
+        let status = board(&[Activity::Idle, Activity::Cruise, Activity::Idle]);
+        let text = render(&status).expect("metrics render");
+        assert_eq!(sample(&text, "aircraft", r#"activity="Idle""#), Some(2.0));
+        assert_eq!(sample(&text, "aircraft", r#"activity="Cruise""#), Some(1.0));
+    }
+
+    #[test]
+    fn concurrent_scrapes_see_every_gauge() {
+        let threads: Vec<_> = [1usize, 2, 3]
+            .into_iter()
+            .map(|idle| {
+                std::thread::spawn(move || {
+                    let status = board(&vec![Activity::Idle; idle]);
+                    for _ in 0..1000 {
+                        let text = render(&status).expect("metrics render");
+                        let count = sample(&text, "aircraft", r#"activity="Idle""#);
+                        assert_eq!(count, Some(idle as f64));
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().expect("scrapes should agree with their board");
+        }
+    }
+}
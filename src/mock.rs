@@ -0,0 +1,239 @@
+//! Local stand-in for svc-telemetry, svc-atc and svc-cargo
+//!
+//! Serves every endpoint the simulator uses from one address, so the
+//!  telemetry, atc and cargo ports all point to the same server:
+//!
+//! | Method | Path                 |                                         |
+//! | ------ | -------------------- | --------------------------------------- |
+//! | GET    | `/telemetry/login`   | issue a token for the identifier in the body |
+//! | POST   | `/telemetry/netrid`  | decode and record a NETRID frame        |
+//! | GET    | `/atc/plans`         | flight plans that were not answered     |
+//! | POST   | `/atc/acknowledge`   | record a flight plan confirmation or denial |
+//! | PUT    | `/cargo/scan`        | record a parcel scan                    |
+//! | GET    | `/records`           | everything recorded so far, as JSON     |
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use svc_atc_client_rest::types::*;
+use svc_cargo_client_rest::types::CargoScan;
+
+use crate::backend::memory::ScanRecord;
+use crate::telemetry::{decode_frame, FrameKind};
+
+/// A NETRID frame received by the mock backend
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameRecord {
+    /// aircraft the token was issued to
+    pub identifier: String,
+    pub kind: FrameKind,
+    /// debug rendering of the decoded message
+    pub message: String,
+    pub payload: Vec<u8>,
+}
+
+/// Everything the mock backend received
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MockRecords {
+    pub logins: Vec<String>,
+    pub frames: Vec<FrameRecord>,
+    pub acknowledged: Vec<String>,
+    pub declined: Vec<String>,
+    pub scans: Vec<ScanRecord>,
+    /// requests that could not be processed
+    pub rejected: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    plans: Vec<FlightPlan>,
+    tokens: HashMap<String, String>,
+    records: MockRecords,
+}
+
+/// Mock backend server
+///
+/// Cheap to clone; clones share the same plans and records.
+#[derive(Debug, Clone, Default)]
+pub struct MockServer {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MockServer {
+    /// A mock backend serving `plans`
+    pub fn new(plans: Vec<FlightPlan>) -> Self {
+        let server = Self::default();
+        server.lock().plans = plans;
+        server
+    }
+
+    /// Copy of everything received so far
+    pub fn records(&self) -> MockRecords {
+        self.lock().records.clone()
+    }
+
+    /// Bind to `addr` and return the bound address with the server future
+    ///
+    /// Binding to port 0 picks a free port.
+    pub fn bind(
+        &self,
+        addr: &SocketAddr,
+    ) -> Result<(SocketAddr, impl Future<Output = Result<(), hyper::Error>>), hyper::Error> {
+        let server = self.clone();
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| handle(server.clone(), req)))
+            }
+        });
+
+        let server = Server::try_bind(addr)?.serve(make_service);
+        Ok((server.local_addr(), server))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // a panic while holding the lock leaves the records intact
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn reject(&self, reason: String) -> Response<Body> {
+        tracing::warn!(%reason, "rejected request");
+        self.lock().records.rejected.push(reason.clone());
+        respond(StatusCode::BAD_REQUEST, reason)
+    }
+}
+
+fn respond(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle(server: MockServer, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let bearer = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.to_string());
+
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return Ok(server.reject(format!("{method} {path}: could not read body: {e}"))),
+    };
+
+    let response = match (method.clone(), path.as_str()) {
+        (Method::GET, "/telemetry/login") => login(&server, &body),
+        (Method::POST, "/telemetry/netrid") => netrid(&server, bearer, &body),
+        (Method::GET, "/atc/plans") => plans(&server),
+        (Method::POST, "/atc/acknowledge") => acknowledge(&server, &body),
+        (Method::PUT, "/cargo/scan") => scan(&server, &body),
+        (Method::GET, "/records") => match serde_json::to_string(&server.records()) {
+            Ok(json) => respond(StatusCode::OK, json),
+            Err(e) => respond(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        _ => respond(StatusCode::NOT_FOUND, format!("{method} {path} not found")),
+    };
+
+    Ok(response)
+}
+
+fn login(server: &MockServer, body: &[u8]) -> Response<Body> {
+    let identifier = String::from_utf8_lossy(body).to_string();
+    let token = uuid::Uuid::new_v4().to_string();
+    tracing::info!(%identifier, "logged in");
+
+    let mut inner = server.lock();
+    inner.tokens.insert(token.clone(), identifier.clone());
+    inner.records.logins.push(identifier);
+
+    // svc-telemetry returns the token as a JSON string
+    respond(StatusCode::OK, format!("\"{token}\""))
+}
+
+fn netrid(server: &MockServer, bearer: Option<String>, body: &[u8]) -> Response<Body> {
+    let identifier = {
+        let inner = server.lock();
+        bearer.and_then(|token| inner.tokens.get(&token).cloned())
+    };
+
+    let Some(identifier) = identifier else {
+        return respond(StatusCode::UNAUTHORIZED, "unknown token".to_string());
+    };
+
+    let (kind, message) = match decode_frame(body) {
+        Ok(decoded) => decoded,
+        Err(e) => return server.reject(format!("({identifier}) {e}")),
+    };
+
+    server.lock().records.frames.push(FrameRecord {
+        identifier,
+        kind,
+        message,
+        payload: body.to_vec(),
+    });
+
+    respond(StatusCode::OK, String::new())
+}
+
+fn plans(server: &MockServer) -> Response<Body> {
+    let inner = server.lock();
+    let plans: Vec<&FlightPlan> = inner
+        .plans
+        .iter()
+        .filter(|p| !inner.records.acknowledged.contains(&p.flight_uuid))
+        .filter(|p| !inner.records.declined.contains(&p.flight_uuid))
+        .collect();
+
+    match serde_json::to_string(&plans) {
+        Ok(json) => respond(StatusCode::OK, json),
+        Err(e) => respond(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+fn acknowledge(server: &MockServer, body: &[u8]) -> Response<Body> {
+    let request = match serde_json::from_slice::<AckRequest>(body) {
+        Ok(request) => request,
+        Err(e) => return server.reject(format!("could not parse acknowledgement: {e}")),
+    };
+
+    let records = &mut server.lock().records;
+    match request.status {
+        AckStatus::Confirm => {
+            tracing::info!(fp_id = %request.fp_id, "flight plan acknowledged");
+            records.acknowledged.push(request.fp_id);
+        }
+        AckStatus::Deny => {
+            tracing::info!(fp_id = %request.fp_id, "flight plan declined");
+            records.declined.push(request.fp_id);
+        }
+    }
+
+    respond(StatusCode::OK, String::new())
+}
+
+fn scan(server: &MockServer, body: &[u8]) -> Response<Body> {
+    let scan = match serde_json::from_slice::<CargoScan>(body) {
+        Ok(scan) => scan,
+        Err(e) => return server.reject(format!("could not parse parcel scan: {e}")),
+    };
+
+    tracing::info!(cargo_id = %scan.cargo_id, scanner_id = %scan.scanner_id, "parcel scanned");
+    server.lock().records.scans.push(ScanRecord {
+        identifier: scan.scanner_id.clone(),
+        scanner_id: scan.scanner_id,
+        cargo_id: scan.cargo_id,
+        latitude: scan.latitude,
+        longitude: scan.longitude,
+        timestamp: scan.timestamp,
+    });
+
+    respond(StatusCode::OK, String::new())
+}
@@ -1,179 +1,178 @@
-use hyper::{
-    body::{Body, Bytes},
-    client::connect::HttpConnector,
-    client::Client,
-    Method, Request, StatusCode,
-};
+use chrono::{DateTime, Utc};
 use svc_atc_client_rest::types::*;
+use svc_cargo_client_rest::types::CargoScan;
 
-use crate::State;
+use crate::clock::SimClock;
+use crate::phase::{self, PhaseOptions};
+use crate::profile::PerformanceProfile;
+use crate::{Activity, State};
 use geo::prelude::*;
 use geo::point;
 
-pub enum OrdersError {
-    // Unauthorized,
-    Other,
-}
-
-impl std::fmt::Display for OrdersError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            // OrdersError::Unauthorized => write!(f, "Unauthorized"),
-            OrdersError::Other => write!(f, "Other"),
+/// Merge freshly received orders into the queue of plans
+///
+/// Orders replace the queued plan with the same session, other orders are
+///  appended. Returns the flight ids of the merged orders so they can be
+///  acknowledged.
+pub fn merge_orders(plans: &mut Vec<FlightPlan>, orders: Vec<FlightPlan>) -> Vec<String> {
+    let mut flight_ids = vec![];
+    for order in orders {
+        flight_ids.push(order.flight_uuid.clone());
+
+        let mut in_place = false;
+        plans.iter_mut().for_each(|p| if p.session_id == order.session_id {
+            *p = order.clone();
+            in_place = true;
+        });
+
+        if !in_place {
+            plans.push(order);
         }
     }
-}
 
-pub(crate) async fn acknowledge_order(
-    client: &Client<HttpConnector>,
-    base_uri: &str,
-    flight_id: &str,
-    identifier: &str
-) -> Result<(), OrdersError> {
-    let url = format!("{base_uri}/acknowledge");
-
-    println!("| {identifier} | confirming flight_id from {url}.");
+    flight_ids
+}
 
-    // acquire plans
-    let data = AckRequest {
-        fp_id: flight_id.to_string(),
-        status: AckStatus::Confirm
-    };
+/// Total horizontal length of a flight plan path in meters
+pub fn plan_distance(plan: &FlightPlan) -> f64 {
+    plan
+        .path
+        .windows(2)
+        .map(|ps| {
+            let p1 = point!(x: ps[0].longitude, y: ps[0].latitude);
+            let p2 = point!(x: ps[1].longitude, y: ps[1].latitude);
+            p1.haversine_distance(&p2)
+        })
+        .sum()
+}
 
-    let data_str = serde_json::to_string(&data).unwrap();
-
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(url)
-        .header("content-type", "application/json")
-        .body(Body::from(data_str))
-        .map_err(|e| {
-            println!("({identifier}) could not build request: {}", e);
-            OrdersError::Other
-        })?;
-
-    let res = client.request(req).await.map_err(|e| {
-        println!("| {identifier} | request to confirm flight plan failed: {}", e);
-        OrdersError::Other
-    })?;
-
-    if res.status() != StatusCode::OK {
-        println!("| {identifier} | could not confirm flight plan: {}", res.status());
-        return Err(OrdersError::Other);
-    };
+/// Distance in meters from `position` along the whole path of `plan`
+pub fn flight_distance(plan: &FlightPlan, position: &PointZ) -> f64 {
+    let to_path = plan.path.first().map_or(0.0, |first| {
+        let p1 = point!(x: position.longitude, y: position.latitude);
+        let p2 = point!(x: first.longitude, y: first.latitude);
+        p1.haversine_distance(&p2)
+    });
 
-    Ok(())
+    to_path + plan_distance(plan)
 }
 
-pub(crate) async fn get_orders(
-    client: &Client<HttpConnector>,
-    base_uri: &str,
-    uuid: String,
-    identifier: &str
-) -> Result<Vec<FlightPlan>, OrdersError> {
-    let url = format!("{base_uri}/plans");
-
-    println!("| {identifier} | acquiring plans from {url}.");
-
-    // acquire plans
-    let req = Request::builder()
-        .method(Method::GET)
-        .uri(url)
-        .header("content-type", "application/json")
-        .body(Bytes::from(uuid.clone()).into())
-        .map_err(|e| {
-            println!("({identifier}) could not build request: {}", e);
-            OrdersError::Other
-        })?;
-
-    let res = client.request(req).await.map_err(|e| {
-        println!("({identifier}) request to acquire plans failed: {}", e);
-        OrdersError::Other
-    })?;
-
-    if res.status() != StatusCode::OK {
-        println!("({identifier}) could not acquire plans: {}", res.status());
-        return Err(OrdersError::Other);
-    };
-
-    // println!("| {identifier} | acquired plans: {:#?}", res);
+/// Total mass of `parcels` in kg
+pub fn parcels_kg(parcels: &[CargoInfo]) -> f64 {
+    parcels.iter().map(|p| p.weight_g as f64 / 1000.0).sum()
+}
 
-    let body = hyper::body::to_bytes(res.into_body()).await.map_err(|e| {
-        println!("({identifier}) could not process token stream: {}", e);
-        OrdersError::Other
-    })?;
+/// A parcel scan waiting to be reported to svc-cargo
+///
+/// Scans keep the position and time at which the parcel was scanned, so
+///  they can be reported late while svc-cargo is unavailable.
+#[derive(Debug, Clone)]
+pub struct ParcelScan {
+    pub cargo_id: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timestamp: DateTime<Utc>,
+}
 
-    let plans = serde_json::from_slice::<Vec<FlightPlan>>(&body).map_err(|e| {
-        println!("({identifier}) could not parse plans: {}", e);
-        OrdersError::Other
-    })?;
+impl ParcelScan {
+    pub fn to_cargo_scan(&self, scanner_id: &str) -> CargoScan {
+        CargoScan {
+            scanner_id: scanner_id.to_string(),
+            cargo_id: self.cargo_id.clone(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            timestamp: self.timestamp,
+        }
+    }
+}
 
-    println!("| {identifier} | acquired {} plans.", plans.len());
-    Ok(plans)
+/// Scan parcels at the current position of the aircraft
+fn scan_parcels(
+    clock: &SimClock,
+    state: &State,
+    cargo_ids: impl Iterator<Item = String>,
+) -> Vec<ParcelScan> {
+    cargo_ids
+        .map(|cargo_id| ParcelScan {
+            cargo_id,
+            latitude: state.position.latitude,
+            longitude: state.position.longitude,
+            timestamp: clock.now(),
+        })
+        .collect()
 }
 
-pub async fn init_plan(
-    client: &Client<HttpConnector>,
+/// Start flying `plan`
+///
+/// On the ground the flight begins with the preflight checks, in hold the
+///  aircraft flies on right away, at a speed within `profile`. Returns the
+///  scans of the parcels picked up at the origin.
+pub fn init_plan(
+    clock: &SimClock,
     state: &mut State,
-    cargo_uri: &str,
     current_tick: u64,
-    plan: FlightPlan
-) {
-    println!("| {} | {current_tick} | new flight plan: {}", state.id, plan.session_id);
-    for parcel in plan.acquire.iter() {
-        let _ = crate::parcel::parcel_scan(
-            &client,
-            &state.id,
-            &state.scanner_id,
-            &parcel.id,
-            state.position.latitude,
-            state.position.longitude,
-            &cargo_uri,
-        ).await;
-    }
-
-    let total_distance: f64 = plan
-        .path
-        .windows(2)
-        .map(|ps| {
-            let p1 = point!(x: ps[0].longitude, y: ps[0].latitude);
-            let p2 = point!(x: ps[1].longitude, y: ps[1].latitude);
-            p1.haversine_distance(&p2)
-        })
-        .sum();
-    
-    let total_duration_ms = (plan.target_timeslot_start.timestamp_millis() as u64) - current_tick;
-    state.ground_velocity_m_s = total_distance / (total_duration_ms as f64 / 1000.0);
+    plan: FlightPlan,
+    profile: &PerformanceProfile,
+) -> Vec<ParcelScan> {
+    tracing::info!(current_tick, session = %plan.session_id, "starting flight plan");
+    let scans = scan_parcels(clock, state, plan.acquire.iter().map(|p| p.id.clone()));
+    state.onboard.extend(plan.acquire.iter().map(|p| p.id.clone()));
+    state.payload_kg += parcels_kg(&plan.acquire);
+    state.diverted = false;
+    state.leg_origin = None;
+
+    state.target_ground_velocity_m_s =
+        profile.ground_velocity(&plan, &state.position, current_tick);
     state.current_plan = Some(plan);
-    state.activity = crate::Activity::Cruise;
+    let next = if state.activity == Activity::Hold {
+        Activity::Climb
+    } else {
+        Activity::Preflight
+    };
+    phase::transition(state, next, current_tick);
+    scans
 }
 
-
-pub async fn end_plan(
-    client: &Client<HttpConnector>,
-    state: &mut State,
-    cargo_uri: &str
-) {
+/// Land at the end of the current plan
+///
+/// The aircraft charges afterwards if `phases` has a charging time or the
+///  battery is not full. A plan that runs out in the air ends with a
+///  landing in place. Returns the scans of the parcels delivered at the
+///  target.
+pub fn end_plan(clock: &SimClock, state: &mut State, phases: &PhaseOptions) -> Vec<ParcelScan> {
     let Some(ref plan) = state.current_plan else {
-        println!("| {} | tried to end a non-existent plan.", state.id);
-        return;
+        tracing::warn!("tried to end a non-existent plan");
+        return vec![];
     };
 
-    println!("| {} | new flight plan: {}", state.id, plan.session_id);
-    for parcel in plan.deliver.iter() {
-        let _ = crate::parcel::parcel_scan(
-            &client,
-            &state.id,
-            &state.scanner_id,
-            &parcel.id,
-            state.position.latitude,
-            state.position.longitude,
-            &cargo_uri,
-        ).await;
-    }
+    // landed short of the target, the parcels stay on board
+    let deliver = if state.diverted { vec![] } else { plan.deliver.clone() };
+    tracing::info!(session = %plan.session_id, diverted = state.diverted, "ending flight plan");
+    let cargo_ids: Vec<String> = deliver.iter().map(|p| p.id.clone()).collect();
+    state.onboard.retain(|id| !cargo_ids.contains(id));
+    state.payload_kg = (state.payload_kg - parcels_kg(&deliver)).max(0.0);
+    let scans = scan_parcels(clock, state, cargo_ids.into_iter());
 
     state.current_plan = None;
-    state.ground_velocity_m_s = 0.0;
-    state.vertical_velocity_m_s = 0.0;
-    state.activity = crate::Activity::Idle;
+    state.diverted = false;
+    let now_ms = clock.now_ms();
+    let next = match state.activity {
+        // touched down on the last point
+        Activity::Landing => phase::after_landing(state, phases),
+        activity if activity.is_airborne() => Activity::Landing,
+        _ => Activity::Idle,
+    };
+
+    if next != Activity::Landing {
+        state.stop();
+    }
+    if !phase::transition(state, next, now_ms) {
+        tracing::warn!(
+            from = ?state.activity,
+            to = ?next,
+            "forcing flight phase after the plan ended"
+        );
+        phase::reset(state, next, now_ms);
+    }
+    scans
 }
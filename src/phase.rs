@@ -0,0 +1,367 @@
+//! Flight phases from preflight to landing
+//!
+//! A flight plan is flown through a fixed sequence of phases: preflight
+//!  checks and spool-up on the pad, a vertical takeoff, climb, cruise and
+//!  descent along the path, an approach at the height of the target and a
+//!  vertical landing. Between plans the aircraft is idle or charging on the
+//!  ground, or holds in the air when its plan was aborted. Transitions are
+//!  checked against [`Activity::can_transition_to`], and each phase has its
+//!  own speed and climb targets, see [`crate::kinematics::steer`].
+
+use geo::point;
+use geo::prelude::*;
+use serde::{Deserialize, Serialize};
+use svc_telemetry_client_rest::netrid_types::OperationalStatus;
+
+use crate::kinematics::TARGET_RADIUS_M;
+use crate::profile::PerformanceProfile;
+use crate::State;
+
+/// Difference in meters to the altitude of the next point within which
+///  the aircraft cruises instead of climbing or descending
+const ALTITUDE_TOLERANCE_M: f64 = 1.0;
+
+/// Height in meters above the target at which a landing touches down
+pub const TOUCHDOWN_M: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activity {
+    /// on the ground without a plan
+    Idle,
+    /// on the ground, checks before the flight
+    Preflight,
+    /// on the ground, rotors spinning up
+    SpoolUp,
+    /// vertical climb to the takeoff height
+    Takeoff,
+    Climb,
+    Cruise,
+    Descent,
+    /// last leg, at the takeoff height above the target
+    Approach,
+    /// vertical descent onto the target
+    Landing,
+    /// hovering in place without a plan
+    Hold,
+    /// on the ground after a flight, until the battery is full
+    Charging,
+    OutOfService,
+}
+
+impl Activity {
+    /// If the aircraft is in the air in this phase
+    pub fn is_airborne(self) -> bool {
+        matches!(
+            self,
+            Activity::Takeoff
+                | Activity::Climb
+                | Activity::Cruise
+                | Activity::Descent
+                | Activity::Approach
+                | Activity::Landing
+                | Activity::Hold
+        )
+    }
+
+    /// If a flight plan can be started in this phase
+    pub fn accepts_plan(self) -> bool {
+        matches!(self, Activity::Idle | Activity::Hold)
+    }
+
+    /// If the phase graph allows moving from this phase to `to`
+    pub fn can_transition_to(self, to: Activity) -> bool {
+        use Activity::*;
+
+        match (self, to) {
+            (_, OutOfService) => true,
+            (OutOfService, Idle | Hold) => true,
+            (Idle, Preflight | Charging) => true,
+            (Charging, Idle | Preflight) => true,
+            (Preflight, SpoolUp | Idle) => true,
+            (SpoolUp, Takeoff | Idle) => true,
+            (Takeoff, Climb) => true,
+            (Climb | Cruise | Descent, Climb | Cruise | Descent | Approach) => true,
+            (Approach, Landing) => true,
+            // landing in place, without a plan
+            (from, Landing) if from.is_airborne() => true,
+            (Landing, Idle | Charging) => true,
+            (Hold, Climb | Cruise | Descent | Approach) => true,
+            (from, Hold) => from.is_airborne(),
+            _ => false,
+        }
+    }
+
+    /// NETRID operational status reported in this phase
+    pub fn operational_status(self) -> OperationalStatus {
+        match self {
+            Activity::Idle | Activity::Preflight | Activity::SpoolUp | Activity::Charging => {
+                OperationalStatus::Ground
+            }
+            Activity::OutOfService => OperationalStatus::Undeclared,
+            _ => OperationalStatus::Airborne,
+        }
+    }
+}
+
+/// Timing and profile of the phases of a flight
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PhaseOptions {
+    pub preflight_ms: u64,
+    pub spool_up_ms: u64,
+    /// height above the pad at which takeoff ends, and above the target at
+    ///  which the approach is flown
+    pub takeoff_height_m: f64,
+    pub takeoff_climb_rate_m_s: f64,
+    /// distance to the target at which the approach begins
+    pub approach_distance_m: f64,
+    pub approach_speed_m_s: f64,
+    pub landing_descent_rate_m_s: f64,
+    /// time on the ground after a flight before the next plan is started,
+    ///  0 to skip charging unless the battery needs it
+    pub charging_ms: u64,
+}
+
+impl Default for PhaseOptions {
+    fn default() -> Self {
+        PhaseOptions {
+            preflight_ms: 5000,
+            spool_up_ms: 3000,
+            takeoff_height_m: 10.0,
+            takeoff_climb_rate_m_s: 2.0,
+            approach_distance_m: 100.0,
+            approach_speed_m_s: 3.0,
+            landing_descent_rate_m_s: 1.0,
+            charging_ms: 0,
+        }
+    }
+}
+
+/// Move the aircraft to phase `to` at `now_ms`
+///
+/// Transitions the phase graph does not allow are logged and refused.
+///  Returns if the aircraft is in phase `to` now.
+pub fn transition(state: &mut State, to: Activity, now_ms: u64) -> bool {
+    let from = state.activity;
+    if from == to {
+        return true;
+    }
+
+    if !from.can_transition_to(to) {
+        tracing::warn!(?from, ?to, "refusing invalid flight phase transition");
+        return false;
+    }
+
+    tracing::info!(?from, ?to, altitude_meters = state.position.altitude_meters, "flight phase");
+    state.activity = to;
+    state.phase_since_ms = now_ms;
+    state.phase_altitude_m = state.position.altitude_meters;
+    true
+}
+
+/// Enter phase `to` without checking the phase graph
+///
+/// For moves the aircraft does not fly itself, like teleports and restores.
+pub fn reset(state: &mut State, to: Activity, now_ms: u64) {
+    state.activity = to;
+    state.phase_since_ms = now_ms;
+    state.phase_altitude_m = state.position.altitude_meters;
+}
+
+/// Advance to the next phase once the current one is complete
+///
+/// Touching down is up to [`crate::telemetry::update_location`], ending
+///  the plan afterwards up to [`crate::orders::end_plan`]. After takeoff
+///  the cruise speed is planned again within `profile`.
+pub fn advance(
+    now_ms: u64,
+    state: &mut State,
+    options: &PhaseOptions,
+    profile: &PerformanceProfile,
+) {
+    let in_phase_ms = now_ms.saturating_sub(state.phase_since_ms);
+
+    let from = state.activity;
+    let next = match from {
+        Activity::Preflight if in_phase_ms >= options.preflight_ms => Activity::SpoolUp,
+        Activity::SpoolUp if in_phase_ms >= options.spool_up_ms => Activity::Takeoff,
+        Activity::Takeoff
+            if state.position.altitude_meters >= state.phase_altitude_m + options.takeoff_height_m =>
+        {
+            Activity::Climb
+        }
+        Activity::Climb | Activity::Cruise | Activity::Descent => match en_route(state, options) {
+            Some(next) => next,
+            None => return,
+        },
+        Activity::Approach if distance_to_next_point(state).is_some_and(|d| d < TARGET_RADIUS_M) => {
+            Activity::Landing
+        }
+        Activity::Charging
+            if in_phase_ms >= options.charging_ms && state.battery.is_none_or(|b| b.is_full()) =>
+        {
+            Activity::Idle
+        }
+        _ => return,
+    };
+
+    if !transition(state, next, now_ms) || from != Activity::Takeoff {
+        return;
+    }
+
+    // the time spent on the pad is made up for with the cruise speed, as
+    //  far as the profile allows
+    if let Some(ref plan) = state.current_plan {
+        state.target_ground_velocity_m_s = profile.ground_velocity(plan, &state.position, now_ms);
+    }
+}
+
+/// Phase on the ground after touching down
+///
+/// The aircraft charges if `options` has a charging time or the battery
+///  is not full.
+pub fn after_landing(state: &State, options: &PhaseOptions) -> Activity {
+    if options.charging_ms > 0 || state.battery.is_some_and(|b| !b.is_full()) {
+        Activity::Charging
+    } else {
+        Activity::Idle
+    }
+}
+
+/// Altitude to fly the leg to the next point at
+///
+/// The last leg is flown at the takeoff height above the target, to
+///  approach it there and land vertically.
+pub fn leg_altitude_m(state: &State, options: &PhaseOptions) -> Option<f64> {
+    let plan = state.current_plan.as_ref()?;
+    let next_point = plan.path.first()?;
+
+    if plan.path.len() == 1 {
+        Some(next_point.altitude_meters + options.takeoff_height_m)
+    } else {
+        Some(next_point.altitude_meters)
+    }
+}
+
+/// Climb, cruise, descent or approach, by the next point of the plan
+fn en_route(state: &State, options: &PhaseOptions) -> Option<Activity> {
+    let plan = state.current_plan.as_ref()?;
+    let distance = distance_to_next_point(state)?;
+
+    if plan.path.len() == 1 && distance <= options.approach_distance_m.max(TARGET_RADIUS_M) {
+        return Some(Activity::Approach);
+    }
+
+    let climb = leg_altitude_m(state, options)? - state.position.altitude_meters;
+    let next = if climb > ALTITUDE_TOLERANCE_M {
+        Activity::Climb
+    } else if climb < -ALTITUDE_TOLERANCE_M {
+        Activity::Descent
+    } else {
+        Activity::Cruise
+    };
+
+    (next != state.activity).then_some(next)
+}
+
+fn distance_to_next_point(state: &State) -> Option<f64> {
+    let next_point = state.current_plan.as_ref()?.path.first()?;
+    let p1 = point!(x: state.position.longitude, y: state.position.latitude);
+    let p2 = point!(x: next_point.longitude, y: next_point.latitude);
+    Some(p1.haversine_distance(&p2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::battery::Battery;
+    use crate::testing::{flight_plan, state};
+
+    #[test]
+    fn allows_the_phases_of_a_flight_in_order() {
+        use Activity::*;
+
+        let flight = [
+            Idle, Preflight, SpoolUp, Takeoff, Climb, Cruise, Descent, Approach, Landing, Charging,
+            Idle,
+        ];
+        assert!(flight.windows(2).all(|w| w[0].can_transition_to(w[1])));
+
+        // no skipping the pad, no climbing out of a landing
+        assert!(!Idle.can_transition_to(Takeoff));
+        assert!(!Preflight.can_transition_to(Climb));
+        assert!(!Landing.can_transition_to(Climb));
+        assert!(!Idle.can_transition_to(Hold));
+
+        // holding and landing in place are only possible in the air
+        assert!(Cruise.can_transition_to(Hold));
+        assert!(Hold.can_transition_to(Landing));
+        assert!(Takeoff.can_transition_to(Landing));
+        assert!(!SpoolUp.can_transition_to(Landing));
+
+        assert!([Idle, Climb, Charging].iter().all(|a| a.can_transition_to(OutOfService)));
+    }
+
+    #[test]
+    fn refuses_transitions_off_the_graph() {
+        let mut state = state();
+        assert!(!transition(&mut state, Activity::Climb, 1000));
+        assert_eq!(state.activity, Activity::Idle);
+        assert_eq!(state.phase_since_ms, 0);
+
+        assert!(transition(&mut state, Activity::Preflight, 1000));
+        assert_eq!(state.activity, Activity::Preflight);
+        assert_eq!(state.phase_since_ms, 1000);
+    }
+
+    #[test]
+    fn advances_once_a_phase_is_complete() {
+        let options = PhaseOptions::default();
+        let profile = PerformanceProfile::default();
+        let start = Utc::now();
+        let now_ms = start.timestamp_millis() as u64;
+        let mut state = state();
+        state.current_plan = Some(flight_plan(start));
+        reset(&mut state, Activity::Preflight, now_ms);
+
+        advance(now_ms + options.preflight_ms - 1, &mut state, &options, &profile);
+        assert_eq!(state.activity, Activity::Preflight);
+        let spool_up_ms = now_ms + options.preflight_ms;
+        advance(spool_up_ms, &mut state, &options, &profile);
+        assert_eq!(state.activity, Activity::SpoolUp);
+        let takeoff_ms = spool_up_ms + options.spool_up_ms;
+        advance(takeoff_ms, &mut state, &options, &profile);
+        assert_eq!(state.activity, Activity::Takeoff);
+
+        // climbs once at the takeoff height, at the speed planned then
+        state.position.altitude_meters = options.takeoff_height_m - 0.5;
+        advance(takeoff_ms + 5000, &mut state, &options, &profile);
+        assert_eq!(state.activity, Activity::Takeoff);
+        state.position.altitude_meters = options.takeoff_height_m;
+        advance(takeoff_ms + 6000, &mut state, &options, &profile);
+        assert_eq!(state.activity, Activity::Climb);
+        assert!(state.target_ground_velocity_m_s >= profile.cruise_speed_m_s);
+    }
+
+    #[test]
+    fn charges_after_landing_with_a_battery_to_fill() {
+        let options = PhaseOptions::default();
+        let mut state = state();
+        assert_eq!(after_landing(&state, &options), Activity::Idle);
+
+        state.battery = Some(Battery {
+            charge_wh: 500.0,
+            ..Battery::full(1000.0)
+        });
+        assert_eq!(after_landing(&state, &options), Activity::Charging);
+
+        let charging = PhaseOptions {
+            charging_ms: 1000,
+            ..options
+        };
+        state.battery = None;
+        assert_eq!(after_landing(&state, &charging), Activity::Charging);
+    }
+}
@@ -0,0 +1,278 @@
+//! Performance profiles of airframe types
+//!
+//! A profile bounds what an aircraft can fly: its speed, climb rate,
+//!  payload and time in the air, and sets the UA type it reports over
+//!  NETRID. Plans are checked against the profile when they are due; plans
+//!  it cannot fly are declined with the reason, see [`check`].
+
+use serde::Deserialize;
+use svc_atc_client_rest::types::*;
+use svc_telemetry_client_rest::netrid_types::UaType;
+
+use crate::kinematics::KinematicLimits;
+use crate::orders::{flight_distance, parcels_kg};
+use crate::phase::PhaseOptions;
+use crate::State;
+
+/// Kind of airframe, reported as the NETRID UA type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Airframe {
+    Aeroplane,
+    #[default]
+    Rotorcraft,
+}
+
+impl Airframe {
+    pub fn ua_type(self) -> UaType {
+        match self {
+            Airframe::Aeroplane => UaType::Aeroplane,
+            Airframe::Rotorcraft => UaType::Rotorcraft,
+        }
+    }
+}
+
+/// Performance of an airframe type
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PerformanceProfile {
+    pub airframe: Airframe,
+    /// plans that need to be flown faster are declined
+    pub max_speed_m_s: f64,
+    /// flown once the target timeslot leaves no time to plan the speed by
+    pub cruise_speed_m_s: f64,
+    /// caps the climb rate of the kinematic limits
+    pub max_climb_rate_m_s: f64,
+    pub payload_capacity_kg: f64,
+    /// longest time in the air
+    pub endurance_ms: u64,
+}
+
+impl Default for PerformanceProfile {
+    fn default() -> Self {
+        PerformanceProfile {
+            airframe: Airframe::Rotorcraft,
+            max_speed_m_s: 25.0,
+            cruise_speed_m_s: 15.0,
+            max_climb_rate_m_s: 5.0,
+            payload_capacity_kg: 5.0,
+            endurance_ms: 1_800_000,
+        }
+    }
+}
+
+impl PerformanceProfile {
+    /// `limits` with the climb rate capped to the profile
+    pub fn limits(&self, limits: &KinematicLimits) -> KinematicLimits {
+        KinematicLimits {
+            max_climb_rate_m_s: limits.max_climb_rate_m_s.min(self.max_climb_rate_m_s),
+            ..*limits
+        }
+    }
+
+    /// Ground velocity to reach the target of `plan` from `position` by the
+    ///  start of its timeslot when departing at `now_ms`
+    ///
+    /// The aircraft flies at least at the cruise speed, a plan due far ahead
+    ///  arrives early rather than stretching its flight, and at most at the
+    ///  maximum speed.
+    pub fn ground_velocity(&self, plan: &FlightPlan, position: &PointZ, now_ms: u64) -> f64 {
+        let start_ms = plan.target_timeslot_start.timestamp_millis() as u64;
+        let planned_m_s = match start_ms.saturating_sub(now_ms) {
+            0 => self.cruise_speed_m_s,
+            left_ms => flight_distance(plan, position) / (left_ms as f64 / 1000.0),
+        };
+
+        planned_m_s.max(self.cruise_speed_m_s).min(self.max_speed_m_s)
+    }
+}
+
+/// Why a plan cannot be flown with a profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Infeasible {
+    /// the target timeslot cannot be met within the maximum speed
+    Speed { required_m_s: f64, max_m_s: f64 },
+    Payload { payload_kg: f64, capacity_kg: f64 },
+    Endurance { flight_ms: u64, endurance_ms: u64 },
+}
+
+impl std::fmt::Display for Infeasible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Infeasible::Speed { required_m_s, .. } if required_m_s.is_infinite() => {
+                write!(f, "no time left to meet the target timeslot")
+            }
+            Infeasible::Speed { required_m_s, max_m_s } => write!(
+                f,
+                "needs {:.1} m/s to meet the target timeslot, at most {:.1} m/s",
+                required_m_s, max_m_s
+            ),
+            Infeasible::Payload { payload_kg, capacity_kg } => write!(
+                f,
+                "payload of {:.2} kg exceeds the capacity of {:.2} kg",
+                payload_kg, capacity_kg
+            ),
+            Infeasible::Endurance { flight_ms, endurance_ms } => write!(
+                f,
+                "flight of {} s exceeds the endurance of {} s",
+                flight_ms / 1000,
+                endurance_ms / 1000
+            ),
+        }
+    }
+}
+
+/// Check that `profile` can fly `plan` when started at `now_ms`
+///
+/// Time on the pad is taken off the time left to fly, as it is by
+///  [`crate::phase::advance`]; aircraft in hold take off right away. The
+///  flight time is the distance from the current position along the path
+///  at the speed the aircraft will fly.
+pub fn check(
+    plan: &FlightPlan,
+    state: &State,
+    now_ms: u64,
+    profile: &PerformanceProfile,
+    phases: &PhaseOptions,
+) -> Result<(), Infeasible> {
+    let payload_kg = state.payload_kg + parcels_kg(&plan.acquire);
+    if payload_kg > profile.payload_capacity_kg {
+        return Err(Infeasible::Payload {
+            payload_kg,
+            capacity_kg: profile.payload_capacity_kg,
+        });
+    }
+
+    let pad_ms = if state.activity.is_airborne() {
+        0
+    } else {
+        phases.preflight_ms + phases.spool_up_ms
+    };
+    let departure_ms = now_ms + pad_ms;
+    let time_left_ms =
+        (plan.target_timeslot_start.timestamp_millis() as u64).saturating_sub(departure_ms);
+    let distance_m = flight_distance(plan, &state.position);
+    let required_m_s = match time_left_ms {
+        0 => f64::INFINITY,
+        _ => distance_m / (time_left_ms as f64 / 1000.0),
+    };
+    if required_m_s > profile.max_speed_m_s {
+        return Err(Infeasible::Speed {
+            required_m_s,
+            max_m_s: profile.max_speed_m_s,
+        });
+    }
+
+    // flown at the cruise speed, or faster to meet the timeslot
+    let speed_m_s = profile.ground_velocity(plan, &state.position, departure_ms);
+    let flight_ms = (distance_m / speed_m_s * 1000.0) as u64;
+    if flight_ms > profile.endurance_ms {
+        return Err(Infeasible::Endurance {
+            flight_ms,
+            endurance_ms: profile.endurance_ms,
+        });
+    }
+
+    if required_m_s > profile.cruise_speed_m_s {
+        tracing::info!(
+            session = %plan.session_id,
+            required_m_s,
+            cruise_speed_m_s = profile.cruise_speed_m_s,
+            "flight plan needs more than the cruise speed"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    use crate::testing::{flight_plan, state};
+
+    fn check_now(profile: &PerformanceProfile) -> Result<(), Infeasible> {
+        let start = Utc::now();
+        let now_ms = start.timestamp_millis() as u64;
+        check(&flight_plan(start), &state(), now_ms, profile, &PhaseOptions::default())
+    }
+
+    #[test]
+    fn flies_plans_within_the_profile() {
+        assert_eq!(check_now(&PerformanceProfile::default()), Ok(()));
+    }
+
+    #[test]
+    fn declines_plans_beyond_the_profile() {
+        // the plan needs about 9 m/s and carries 2 kg
+        let slow = PerformanceProfile {
+            max_speed_m_s: 5.0,
+            ..Default::default()
+        };
+        assert!(matches!(check_now(&slow), Err(Infeasible::Speed { max_m_s: 5.0, .. })));
+
+        let light = PerformanceProfile {
+            payload_capacity_kg: 1.0,
+            ..Default::default()
+        };
+        let heavy = Err(Infeasible::Payload {
+            payload_kg: 2.0,
+            capacity_kg: 1.0,
+        });
+        assert_eq!(check_now(&light), heavy);
+
+        let short = PerformanceProfile {
+            endurance_ms: 10_000,
+            ..Default::default()
+        };
+        assert!(matches!(check_now(&short), Err(Infeasible::Endurance { .. })));
+    }
+
+    #[test]
+    fn declines_plans_with_no_time_left() {
+        let start = Utc::now();
+        let now_ms = (start + Duration::seconds(200)).timestamp_millis() as u64;
+        let (profile, phases) = (PerformanceProfile::default(), PhaseOptions::default());
+        let result = check(&flight_plan(start), &state(), now_ms, &profile, &phases);
+        let Err(infeasible) = result else {
+            panic!("plan should be infeasible");
+        };
+        assert_eq!(infeasible.to_string(), "no time left to meet the target timeslot");
+    }
+
+    #[test]
+    fn plans_the_speed_between_cruise_and_maximum() {
+        let start = Utc::now();
+        let now_ms = start.timestamp_millis() as u64;
+        let position = state().position;
+        let profile = PerformanceProfile {
+            cruise_speed_m_s: 5.0,
+            max_speed_m_s: 20.0,
+            ..Default::default()
+        };
+
+        // about 1 km in two minutes
+        let mut plan = flight_plan(start);
+        let speed = profile.ground_velocity(&plan, &position, now_ms);
+        assert!(speed > 5.0 && speed < 20.0, "{speed} m/s");
+
+        plan.target_timeslot_start = start + Duration::hours(2);
+        assert_eq!(profile.ground_velocity(&plan, &position, now_ms), 5.0);
+
+        plan.target_timeslot_start = start + Duration::seconds(10);
+        assert_eq!(profile.ground_velocity(&plan, &position, now_ms), 20.0);
+    }
+
+    #[test]
+    fn caps_the_climb_rate() {
+        let profile = PerformanceProfile {
+            max_climb_rate_m_s: 1.5,
+            ..Default::default()
+        };
+        let limits = profile.limits(&KinematicLimits::default());
+        assert_eq!(limits.max_climb_rate_m_s, 1.5);
+        assert_eq!(limits.max_descent_rate_m_s, KinematicLimits::default().max_descent_rate_m_s);
+        assert_eq!(profile.airframe.ua_type(), UaType::Rotorcraft);
+    }
+}
@@ -0,0 +1,348 @@
+//! Backing off from services that are unavailable
+//!
+//! Every aircraft backs off from each service on its own with a [`Backoff`];
+//!  the [`Breakers`] are shared by the whole fleet and stop every aircraft
+//!  from calling an endpoint that keeps failing. Neither sleeps, so the
+//!  aircraft keep flying while a service is unavailable.
+
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::BackendError;
+use crate::metrics::metrics;
+
+/// Retry policy for every backend call
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// delay after the first failure
+    pub initial_ms: u64,
+    /// the delay doubles with every consecutive failure up to this
+    pub max_ms: u64,
+    /// each delay is randomly stretched or shrunk by up to this share
+    pub jitter: f64,
+    /// consecutive failures of an endpoint that open its circuit breaker
+    pub breaker_threshold: u32,
+    /// how long an open circuit breaker rejects calls before letting a
+    ///  trial call through
+    pub breaker_open_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_ms: 500,
+            max_ms: 30_000,
+            jitter: 0.2,
+            breaker_threshold: 5,
+            breaker_open_ms: 30_000,
+        }
+    }
+}
+
+/// Exponential backoff with jitter after consecutive failures
+///
+/// Callers check [`Backoff::ready`] before an attempt and report its
+///  outcome.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    failures: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Backoff {
+            policy,
+            failures: 0,
+            next_attempt: None,
+        }
+    }
+
+    /// If the next attempt may be made now
+    pub fn ready(&self) -> bool {
+        match self.next_attempt {
+            Some(next_attempt) => Instant::now() >= next_attempt,
+            None => true,
+        }
+    }
+
+    /// Consecutive failures since the last success
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Record a failed attempt and return the delay before the next one
+    pub fn failure(&mut self) -> Duration {
+        let factor = 2u64.saturating_pow(self.failures);
+        let base_ms = self.policy.initial_ms.saturating_mul(factor).min(self.policy.max_ms);
+
+        let jitter = self.policy.jitter.clamp(0.0, 1.0);
+        let stretch = if jitter > 0.0 {
+            1.0 + rand::thread_rng().gen_range(-jitter..=jitter)
+        } else {
+            1.0
+        };
+
+        let delay = Duration::from_millis((base_ms as f64 * stretch) as u64);
+        self.failures = self.failures.saturating_add(1);
+        self.next_attempt = Some(Instant::now() + delay);
+        delay
+    }
+
+    /// Record a successful attempt
+    pub fn success(&mut self) {
+        self.failures = 0;
+        self.next_attempt = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// a single trial call is on its way, another one is let through
+    ///  if it has not reported back by `until`
+    HalfOpen { until: Instant },
+}
+
+/// Circuit breaker of a single endpoint
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    open_for: Duration,
+    state: BreakerState,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: &RetryPolicy) -> Self {
+        CircuitBreaker {
+            threshold: policy.breaker_threshold.max(1),
+            open_for: Duration::from_millis(policy.breaker_open_ms),
+            state: BreakerState::Closed { failures: 0 },
+        }
+    }
+
+    /// If a call may be made now
+    ///
+    /// Once an open breaker has waited long enough, a single trial call is
+    ///  let through; its outcome closes or reopens the breaker.
+    pub fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        match self.state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } | BreakerState::HalfOpen { until } if now >= until => {
+                self.state = BreakerState::HalfOpen {
+                    until: now + self.open_for,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(self.state, BreakerState::Closed { .. })
+    }
+
+    pub fn success(&mut self) {
+        self.state = BreakerState::Closed { failures: 0 };
+    }
+
+    pub fn failure(&mut self) {
+        let open = BreakerState::Open {
+            until: Instant::now() + self.open_for,
+        };
+
+        self.state = match self.state {
+            BreakerState::Closed { failures } if failures + 1 < self.threshold => {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => open,
+        };
+    }
+}
+
+/// Circuit breakers of every endpoint, shared by the fleet
+///
+/// Endpoints are the labels of the `http_request_duration_seconds` metric,
+///  so an outage of svc-atc does not stop telemetry or parcel scans.
+#[derive(Debug, Clone)]
+pub struct Breakers {
+    policy: RetryPolicy,
+    inner: Arc<Mutex<HashMap<&'static str, CircuitBreaker>>>,
+}
+
+impl Breakers {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Breakers {
+            policy,
+            inner: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<&'static str, CircuitBreaker>> {
+        // breakers stay usable if a thread panicked while holding the lock
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// If a call to `endpoint` may be made now
+    pub fn allow(&self, endpoint: &'static str) -> bool {
+        let policy = self.policy;
+        self.lock()
+            .entry(endpoint)
+            .or_insert_with(|| CircuitBreaker::new(&policy))
+            .allow()
+    }
+
+    /// Record the outcome of a call to `endpoint`
+    ///
+    /// Only transient errors count as failures; a service that answers,
+    ///  even with a rejection, is up.
+    pub fn record<T>(&self, endpoint: &'static str, result: &Result<T, BackendError>) {
+        let policy = self.policy;
+        let mut breakers = self.lock();
+        let breaker = breakers
+            .entry(endpoint)
+            .or_insert_with(|| CircuitBreaker::new(&policy));
+
+        let was_open = breaker.is_open();
+        match result {
+            Err(e) if e.is_transient() => breaker.failure(),
+            _ => breaker.success(),
+        }
+
+        let is_open = breaker.is_open();
+        if is_open != was_open {
+            if is_open {
+                tracing::warn!(endpoint, "circuit breaker opened");
+            } else {
+                tracing::info!(endpoint, "circuit breaker closed");
+            }
+        }
+
+        metrics()
+            .circuit_breaker_open
+            .with_label_values(&[endpoint])
+            .set(is_open as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_ms: 100,
+            max_ms: 1_000,
+            jitter: 0.2,
+            breaker_threshold: 3,
+            breaker_open_ms: 20,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_within_the_jitter_up_to_the_maximum() {
+        let mut backoff = Backoff::new(policy());
+        for base_ms in [100, 200, 400, 800, 1_000, 1_000, 1_000] {
+            let delay_ms = backoff.failure().as_millis() as u64;
+            // the stretched delay is truncated to whole milliseconds
+            assert!(delay_ms + 1 >= base_ms * 8 / 10, "{delay_ms} ms below {base_ms} ms");
+            assert!(delay_ms <= base_ms * 12 / 10, "{delay_ms} ms above {base_ms} ms");
+        }
+
+        assert_eq!(backoff.failures(), 7);
+        assert!(!backoff.ready());
+    }
+
+    #[test]
+    fn backoff_without_jitter_is_exact_and_resets_on_success() {
+        let mut backoff = Backoff::new(RetryPolicy {
+            jitter: 0.0,
+            ..policy()
+        });
+        assert!(backoff.ready());
+        assert_eq!(backoff.failure(), Duration::from_millis(100));
+        assert_eq!(backoff.failure(), Duration::from_millis(200));
+
+        backoff.success();
+        assert!(backoff.ready());
+        assert_eq!(backoff.failures(), 0);
+        assert_eq!(backoff.failure(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn backoff_caps_many_failures_at_the_maximum() {
+        let mut backoff = Backoff::new(RetryPolicy {
+            jitter: 0.0,
+            ..policy()
+        });
+        for _ in 0..100 {
+            backoff.failure();
+        }
+
+        assert_eq!(backoff.failure(), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn breaker_opens_after_the_threshold() {
+        let mut breaker = CircuitBreaker::new(&policy());
+        for _ in 0..2 {
+            breaker.failure();
+            assert!(!breaker.is_open());
+            assert!(breaker.allow());
+        }
+
+        breaker.failure();
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn breaker_lets_a_single_trial_call_through_once_open_long_enough() {
+        let mut breaker = CircuitBreaker::new(&policy());
+        for _ in 0..3 {
+            breaker.failure();
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        assert!(matches!(breaker.state, BreakerState::HalfOpen { .. }));
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn breaker_closes_after_a_successful_trial_call() {
+        let mut breaker = CircuitBreaker::new(&policy());
+        for _ in 0..3 {
+            breaker.failure();
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        breaker.success();
+        assert!(!breaker.is_open());
+        assert!(matches!(breaker.state, BreakerState::Closed { failures: 0 }));
+    }
+
+    #[test]
+    fn breaker_reopens_after_a_failed_trial_call() {
+        let mut breaker = CircuitBreaker::new(&policy());
+        for _ in 0..3 {
+            breaker.failure();
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        breaker.failure();
+        assert!(matches!(breaker.state, BreakerState::Open { .. }));
+        assert!(!breaker.allow());
+    }
+}
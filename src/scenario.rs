@@ -0,0 +1,238 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::aircraft::AircraftConfig;
+use crate::backend::amqp::{AmqpConfig, AmqpOrdersConfig};
+use crate::backend::http::HttpOptions;
+use crate::battery::BatteryOptions;
+use crate::checkpoint::CheckpointOptions;
+use crate::kinematics::KinematicLimits;
+use crate::link::LinkOptions;
+use crate::phase::PhaseOptions;
+use crate::profile::PerformanceProfile;
+use crate::retry::RetryPolicy;
+use crate::shutdown::ShutdownOptions;
+use crate::uas_id::SerialError;
+use crate::wind::WindOptions;
+
+/// Scenario file
+///
+/// Every section is optional; missing values fall back to the defaults
+///  below and can be overridden by command line flags or environment
+///  variables.
+///
+/// ```json
+/// {
+///     "services": { "host": "0.0.0.0", "tlm_port": 8011, "atc_port": 8012, "cargo_port": 8013 },
+///     "http": { "connect_timeout_ms": 2000, "request_timeout_ms": 10000, "pool_max_idle_per_host": 32, "https": false, "ca_bundle": null },
+///     "telemetry": { "id_interval_ms": 2000, "position_interval_ms": 500, "order_poll_interval_ms": 15000, "token_ttl_ms": 3600000 },
+///     "sim": { "tick_ms": 50, "status_interval_ms": 5000, "speed": 1.0, "fast_forward": false },
+///     "kinematics": { "max_acceleration_m_s2": 2.0, "max_deceleration_m_s2": 2.5, "max_jerk_m_s3": 2.0, "max_turn_rate_deg_s": 30.0, "max_climb_rate_m_s": 5.0, "max_descent_rate_m_s": 3.0, "max_vertical_acceleration_m_s2": 1.0 },
+///     "phases": { "preflight_ms": 5000, "spool_up_ms": 3000, "takeoff_height_m": 10.0, "takeoff_climb_rate_m_s": 2.0, "approach_distance_m": 100.0, "approach_speed_m_s": 3.0, "landing_descent_rate_m_s": 1.0, "charging_ms": 0 },
+///     "battery": { "capacity_wh": 1000.0, "empty_mass_kg": 15.0, "hover_power_w_per_kg": 100.0, "drag_power_w_s3_m3": 0.4, "climb_efficiency": 0.7, "ground_power_w": 20.0, "charge_power_w": 1000.0, "warning_pct": 30.0, "reserve_pct": 15.0 },
+///     "profiles": { "heavy": { "airframe": "rotorcraft", "max_speed_m_s": 18.0, "cruise_speed_m_s": 12.0, "max_climb_rate_m_s": 3.0, "payload_capacity_kg": 20.0, "endurance_ms": 1200000 } },
+///     "wind": { "mode": "layered", "layers": [{ "altitude_m": 0.0, "speed_m_s": 3.0, "from_deg": 240.0 }, { "altitude_m": 100.0, "speed_m_s": 8.0, "from_deg": 260.0 }], "gust_m_s": 2.0, "gust_period_ms": 3000, "seed": 1 },
+///     "output": { "mode": "http", "amqp": { "uri": "amqp://...", "exchange": "telemetry" } },
+///     "orders": { "amqp": { "uri": "amqp://...", "queue": "flight_plans.{uuid}" } },
+///     "control": { "host": "0.0.0.0", "port": 8080 },
+///     "retry": { "initial_ms": 500, "max_ms": 30000, "jitter": 0.2, "breaker_threshold": 5, "breaker_open_ms": 30000 },
+///     "links": { "telemetry_timeout_ms": 2000, "orders_timeout_ms": 10000, "cargo_timeout_ms": 10000, "telemetry_queue": 64 },
+///     "shutdown": { "mode": "fly", "max_fly_ms": 60000, "decline_queued": false, "flush_timeout_ms": 5000 },
+///     "checkpoint": { "path": "checkpoint.json", "interval_ms": 10000, "resume": false },
+///     "aircraft": [
+///         { "name": "Mantis", "uuid": "...", "scanner_id": "...", "longitude": 4.9, "latitude": 52.3, "profile": "heavy" }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub services: Services,
+    pub http: HttpOptions,
+    pub telemetry: Rates,
+    pub sim: SimOptions,
+    pub kinematics: KinematicLimits,
+    pub phases: PhaseOptions,
+    /// energy is only simulated with a battery section
+    pub battery: Option<BatteryOptions>,
+    /// performance profiles by name, selected per aircraft
+    pub profiles: HashMap<String, PerformanceProfile>,
+    pub wind: WindOptions,
+    pub output: OutputOptions,
+    pub orders: OrderIntake,
+    pub control: ControlOptions,
+    pub retry: RetryPolicy,
+    pub links: LinkOptions,
+    pub shutdown: ShutdownOptions,
+    pub checkpoint: CheckpointOptions,
+    pub aircraft: Vec<AircraftConfig>,
+}
+
+/// Location of the backend services
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Services {
+    pub host: String,
+    pub tlm_port: Option<u16>,
+    pub atc_port: Option<u16>,
+    pub cargo_port: Option<u16>,
+}
+
+impl Default for Services {
+    fn default() -> Self {
+        Services {
+            host: "0.0.0.0".to_string(),
+            tlm_port: None,
+            atc_port: None,
+            cargo_port: None,
+        }
+    }
+}
+
+/// How often each aircraft reports to and polls the backend
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Rates {
+    pub id_interval_ms: u64,
+    pub position_interval_ms: u64,
+    pub order_poll_interval_ms: u64,
+    /// lifetime of a svc-telemetry token, refreshed at 90% of it
+    pub token_ttl_ms: u64,
+}
+
+impl Default for Rates {
+    fn default() -> Self {
+        Rates {
+            id_interval_ms: 2000,
+            position_interval_ms: 500,
+            order_poll_interval_ms: 15000,
+            token_ttl_ms: 3_600_000,
+        }
+    }
+}
+
+/// General simulation options
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SimOptions {
+    pub tick_ms: u64,
+    pub status_interval_ms: u64,
+
+    /// simulation time speed-up factor, 1.0 is real time
+    pub speed: f64,
+
+    /// step the clock by `tick_ms` as fast as possible instead of in real time
+    pub fast_forward: bool,
+}
+
+impl Default for SimOptions {
+    fn default() -> Self {
+        SimOptions {
+            tick_ms: 50,
+            status_interval_ms: 5000,
+            speed: 1.0,
+            fast_forward: false,
+        }
+    }
+}
+
+/// Where telemetry frames are sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// post to svc-telemetry
+    #[default]
+    Http,
+    /// publish to an AMQP exchange only
+    Amqp,
+    /// post to svc-telemetry and publish to an AMQP exchange
+    Both,
+}
+
+/// Telemetry output options
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    pub mode: OutputMode,
+    pub amqp: AmqpConfig,
+}
+
+/// How aircraft receive flight plans besides polling svc-atc
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OrderIntake {
+    /// subscribe to a per-aircraft AMQP queue of flight plans
+    pub amqp: Option<AmqpOrdersConfig>,
+}
+
+/// Embedded control and inspection API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ControlOptions {
+    pub host: String,
+    /// the API is only served when a port is set
+    pub port: Option<u16>,
+}
+
+impl Default for ControlOptions {
+    fn default() -> Self {
+        ControlOptions {
+            host: "0.0.0.0".to_string(),
+            port: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    MissingPort(&'static str),
+    NoAircraft,
+    InvalidSpeed(f64),
+    /// an interval or time step of 0 ms
+    ZeroInterval(&'static str),
+    InvalidSerial(String, SerialError),
+    NoCheckpoint,
+    UnknownProfile(String, String),
+}
+
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "could not read scenario file: {}", e),
+            ScenarioError::Parse(e) => write!(f, "could not parse scenario file: {}", e),
+            ScenarioError::MissingPort(service) => {
+                write!(f, "no port configured for the {} service", service)
+            }
+            ScenarioError::NoAircraft => write!(f, "no aircraft configured"),
+            ScenarioError::InvalidSpeed(speed) => {
+                write!(f, "sim speed must be greater than zero, got {}", speed)
+            }
+            ScenarioError::ZeroInterval(name) => write!(f, "{} must be greater than zero", name),
+            ScenarioError::InvalidSerial(name, e) => {
+                write!(f, "invalid serial number for {}: {}", name, e)
+            }
+            ScenarioError::NoCheckpoint => write!(f, "resume requires a checkpoint path"),
+            ScenarioError::UnknownProfile(name, profile) => {
+                write!(f, "unknown performance profile {} for {}", profile, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScenarioError::Io(e) => Some(e),
+            ScenarioError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Load a scenario file
+pub fn load(path: &str) -> Result<Scenario, ScenarioError> {
+    let contents = std::fs::read_to_string(path).map_err(ScenarioError::Io)?;
+    serde_json::from_str(&contents).map_err(ScenarioError::Parse)
+}
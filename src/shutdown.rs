@@ -0,0 +1,157 @@
+//! Graceful shutdown on SIGINT and SIGTERM
+//!
+//! On the first signal every aircraft stops taking flight plans, gets to
+//!  the ground according to [`ShutdownMode`], sends a final Location frame
+//!  with `OperationalStatus::Ground` and returns a [`ShutdownReport`]. A
+//!  second signal exits right away.
+
+use serde::{Deserialize, Serialize};
+use svc_atc_client_rest::types::PointZ;
+use tokio::sync::watch;
+
+/// How an aircraft flying a plan gets to the ground on shutdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownMode {
+    /// keep flying the current plan to its target, teleport there if it
+    ///  takes longer than `max_fly_ms`
+    #[default]
+    Fly,
+    /// drop the current plan and move to the ground at its target right away
+    Teleport,
+}
+
+/// What aircraft do on shutdown
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ShutdownOptions {
+    pub mode: ShutdownMode,
+    /// wall clock time to finish the current plan in `fly` mode
+    pub max_fly_ms: u64,
+    /// decline queued flight plans, and plans that arrive during shutdown,
+    ///  with svc-atc
+    pub decline_queued: bool,
+    /// wall clock time to deliver the final frame and the declines
+    pub flush_timeout_ms: u64,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        ShutdownOptions {
+            mode: ShutdownMode::Fly,
+            max_fly_ms: 60_000,
+            decline_queued: false,
+            flush_timeout_ms: 5000,
+        }
+    }
+}
+
+/// Shutdown signal of the fleet, with the options applied when it fires
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    pub signal: watch::Receiver<bool>,
+    pub options: ShutdownOptions,
+}
+
+impl Shutdown {
+    /// If the shutdown was requested
+    pub fn requested(&self) -> bool {
+        *self.signal.borrow()
+    }
+}
+
+/// Fire on SIGINT or SIGTERM, and exit on the second signal
+pub fn listen(options: ShutdownOptions) -> Shutdown {
+    let (tx, signal) = watch::channel(false);
+
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        tracing::info!("shutdown requested, landing the fleet; signal again to exit right away");
+        tx.send_replace(true);
+
+        wait_for_signal().await;
+        tracing::warn!("second signal, exiting without landing");
+        std::process::exit(130);
+    });
+
+    Shutdown { signal, options }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            tracing::error!(error = %e, "could not listen for SIGTERM");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// How an aircraft got to the ground
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Landing {
+    /// no plan was being flown
+    Idle,
+    /// finished the current plan
+    Arrived,
+    /// moved to the target of the current plan, or to the ground below
+    ///  without one
+    Teleported,
+    /// held in the air without a plan and landed below
+    InPlace,
+}
+
+/// Final state of an aircraft after shutdown
+#[derive(Debug, Clone, Serialize)]
+pub struct ShutdownReport {
+    pub name: String,
+    pub landing: Landing,
+    pub position: PointZ,
+    /// flight plans declined with svc-atc
+    pub declined: usize,
+    /// the links handled the final frame and the declines before
+    ///  `flush_timeout_ms`
+    pub flushed: bool,
+}
+
+/// Log the reports of the fleet
+pub fn summarize(reports: &[ShutdownReport]) {
+    for report in reports {
+        tracing::info!(
+            name = %report.name,
+            landing = ?report.landing,
+            latitude = report.position.latitude,
+            longitude = report.position.longitude,
+            altitude_meters = report.position.altitude_meters,
+            declined = report.declined,
+            flushed = report.flushed,
+            "aircraft shut down"
+        );
+    }
+
+    let count = |landing| reports.iter().filter(|r| r.landing == landing).count();
+    tracing::info!(
+        aircraft = reports.len(),
+        idle = count(Landing::Idle),
+        arrived = count(Landing::Arrived),
+        teleported = count(Landing::Teleported),
+        in_place = count(Landing::InPlace),
+        declined = reports.iter().map(|r| r.declined).sum::<usize>(),
+        unflushed = reports.iter().filter(|r| !r.flushed).count(),
+        "fleet shut down"
+    );
+}
@@ -1,155 +1,114 @@
-use hyper::{
-    body::{Body, Bytes},
-    client::connect::HttpConnector,
-    client::Client,
-    Method, Request, StatusCode,
-};
-use packed_struct::PackedStruct;
+use chrono::{DateTime, Utc};
+use packed_struct::{PackedStruct, PackedStructSlice};
+use serde::Serialize;
 use svc_telemetry_client_rest::netrid_types::*;
 use geo::prelude::*;
 use geo::point;
 
-use crate::{State, Activity};
-
-pub enum NetworkError {
-    Unauthorized,
-    Other,
+use crate::error::EncodeError;
+use crate::kinematics::{self, KinematicLimits};
+use crate::uas_id;
+use crate::wind::{Velocity, Wind};
+use crate::phase::{self, Activity, PhaseOptions};
+use crate::State;
+
+/// Kind of NETRID message carried by a [`TelemetryFrame`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FrameKind {
+    Basic,
+    Location,
 }
 
-impl std::fmt::Display for NetworkError {
+impl std::fmt::Display for FrameKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            NetworkError::Unauthorized => write!(f, "Unauthorized"),
-            NetworkError::Other => write!(f, "Other"),
+            FrameKind::Basic => write!(f, "id"),
+            FrameKind::Location => write!(f, "position"),
         }
     }
 }
 
-pub(crate) async fn acquire_token(
-    client: &Client<HttpConnector>,
-    base_url: &str,
-    identifier: String,
-) -> Result<String, NetworkError> {
-    let url = format!("{base_url}/login");
-
-    println!("| {identifier} | acquiring token from {url}.");
-    // acquire token
-    let req = Request::builder()
-        .method(Method::GET)
-        .uri(url)
-        .header("content-type", "text/plain")
-        .body(Bytes::from(identifier.clone()).into())
-        .unwrap();
-
-    let res = client.request(req).await.map_err(|e| {
-        println!("({identifier}) could not acquire token: {}", e);
-        NetworkError::Other
-    })?;
-
-    if res.status() != StatusCode::OK {
-        println!("({identifier}) could not acquire token: {}", res.status());
-        return Err(NetworkError::Unauthorized);
-    };
-
-    let body = hyper::body::to_bytes(res.into_body()).await.map_err(|e| {
-        println!("({identifier}) could not process token stream: {}", e);
-        NetworkError::Other
-    })?;
-
-    let token = String::from_utf8(body.to_vec())
-        .map_err(|e| {
-            println!("({identifier}) could not convert token to string: {}", e);
-            NetworkError::Other
-        })?
-        .trim_matches('"')
-        .replace("\"", "");
-
-    println!("| {identifier} | acquired token.");
-    Ok(token)
+/// A packed NETRID frame ready to be handed to a telemetry sink
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryFrame {
+    /// aircraft that produced the frame
+    pub identifier: String,
+    pub kind: FrameKind,
+    /// simulation time at which the frame was built
+    pub timestamp: DateTime<Utc>,
+    pub payload: Vec<u8>,
 }
 
-pub(crate) async fn id_update(
-    client: &Client<HttpConnector>,
-    url: &str,
+/// Build a Basic ID frame for an aircraft of `ua_type`
+///
+/// See [`uas_id::encode`] for how `uas_id` is mapped to the UAS ID field.
+pub fn id_frame(
+    identifier: &str,
+    ua_type: UaType,
     id_type: IdType,
     uas_id: &str,
-    token: &str,
-) -> Result<(), NetworkError> {
+    now: DateTime<Utc>,
+) -> Result<TelemetryFrame, EncodeError> {
     // issue id update
-    let Ok(uas_id_formatted) = <[u8; 20]>::try_from(format!("{:>20}", uas_id).as_ref()) else {
-        panic!("({uas_id} could not convert identifier to [u8; 20]");
-    };
+    let uas_id_formatted = uas_id::encode(id_type, uas_id)?;
 
     // build NETRID Packet
-    let Ok(message) = BasicMessage {
-        ua_type: UaType::Rotorcraft,
+    let message = BasicMessage {
+        ua_type,
         id_type,
         uas_id: uas_id_formatted,
         ..Default::default()
     }
-    .pack() else {
-        panic!("({uas_id} could not pack BasicMessage");
-    };
+    .pack()
+    .map_err(|source| EncodeError::Pack { message: "BasicMessage", source })?;
 
-    let Ok(payload) = Frame {
+    let payload = Frame {
         header: Header {
             message_type: MessageType::Basic,
             ..Default::default()
         },
         message,
     }
-    .pack() else {
-        panic!("({uas_id} could not pack Frame");
-    };
-
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(format!("{url}/netrid"))
-        .header("content-type", "application/octet-stream")
-        .header("Authorization", format!("Bearer {token}"))
-        .body(Body::from(payload.to_vec()))
-        .unwrap();
-
-    let result = client.request(req).await.map_err(|e| {
-        println!("({uas_id}) could not issue id update: {}", e);
-        NetworkError::Other
-    })?;
-
-    if result.status() != StatusCode::OK {
-        println!(
-            "({uas_id}) could not issue id update: {}",
-            result.status()
-        );
-        return Err(NetworkError::Unauthorized);
-    }
-
-    // println!("({uas_id}) response {:#?}.", result);
-    // println!("({uas_id}) issued id update.");
-    Ok(())
+    .pack()
+    .map_err(|source| EncodeError::Pack { message: "Frame", source })?;
+
+    Ok(TelemetryFrame {
+        identifier: identifier.to_string(),
+        kind: FrameKind::Basic,
+        timestamp: now,
+        payload: payload.to_vec(),
+    })
 }
 
-/// Issue position update to network
-pub(crate) async fn position_update(client: &Client<HttpConnector>, url: &str, token: &str, state: &State) -> Result<(), NetworkError> {
+/// Build a Location frame from the current state of the aircraft
+pub fn location_frame(state: &State, now: DateTime<Utc>) -> Result<TelemetryFrame, EncodeError> {
     let altitude = LocationMessage::encode_altitude(state.position.altitude_meters as f32);
 
-    let Ok((ew_direction, track_direction)) = LocationMessage::encode_direction(state.track_angle_deg as u16) else {
-        panic!("({}) could not encode direction", state.id);
-    };
-
-    // println!("| {} | ew_direction: {:?}, track_direction: {}", state.id, ew_direction, track_direction);
-
-    let Ok((speed_multiplier, speed)) = LocationMessage::encode_speed(state.ground_velocity_m_s as f32) else {
-        panic!("({}) could not encode speed", state.id);
-    };
+    let (ew_direction, track_direction) =
+        LocationMessage::encode_direction(state.track_angle_deg as u16).map_err(|_| {
+            EncodeError::Field {
+                field: "direction",
+                value: format!("{:.1} deg", state.track_angle_deg),
+            }
+        })?;
+
+    let (speed_multiplier, speed) =
+        LocationMessage::encode_speed(state.ground_velocity_m_s as f32).map_err(|_| {
+            EncodeError::Field {
+                field: "speed",
+                value: format!("{:.1} m/s", state.ground_velocity_m_s),
+            }
+        })?;
 
     let vertical_speed = LocationMessage::encode_vertical_speed(state.vertical_velocity_m_s as f32);
     let latitude = LocationMessage::encode_latitude(state.position.latitude);
     let longitude = LocationMessage::encode_longitude(state.position.longitude);
-    let Ok(timestamp) = LocationMessage::encode_timestamp(chrono::Utc::now()) else {
-        panic!("({}) could not encode timestamp", state.id);
-    };
+    let timestamp = LocationMessage::encode_timestamp(now).map_err(|_| EncodeError::Field {
+        field: "timestamp",
+        value: now.to_rfc3339(),
+    })?;
 
-    let Ok(message) = LocationMessage {
+    let message = LocationMessage {
         speed,
         speed_multiplier,
         speed_accuracy: SpeedAccuracyMetersPerSecond::Lt1,
@@ -167,92 +126,97 @@ pub(crate) async fn position_update(client: &Client<HttpConnector>, url: &str, t
         horizontal_accuracy: HorizontalAccuracyMeters::Lt1,
         timestamp,
         timestamp_accuracy: 0.into(),
-        operational_status: match state.activity {
-            Activity::Idle => OperationalStatus::Ground,
-            Activity::Cruise => OperationalStatus::Airborne,
+        operational_status: if state.diverted && state.activity.is_airborne() {
+            OperationalStatus::Emergency
+        } else {
+            state.activity.operational_status()
         },
         reserved_0: 0.into(),
         reserved_1: 0.into(),
         reserved_2: 0,
     }
-    .pack() else {
-        panic!("({}) could not pack LocationMessage", state.id);
-    };
+    .pack()
+    .map_err(|source| EncodeError::Pack { message: "LocationMessage", source })?;
 
-    let Ok(payload) = Frame {
+    let payload = Frame {
         header: Header {
             message_type: MessageType::Location,
             ..Default::default()
         },
         message,
     }
-    .pack() else {
-        panic!("({}) could not pack location frame", state.id);
-    };
-
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(format!("{url}/netrid"))
-        .header("content-type", "application/octet-stream")
-        .header("Authorization", format!("Bearer {token}"))
-        .body(Body::from(payload.to_vec()))
-        .unwrap();
-
-    let result = client.request(req).await.map_err(|e| {
-        println!("({}) could not issue position update: {}", state.id, e);
-        NetworkError::Other
-    })?;
+    .pack()
+    .map_err(|source| EncodeError::Pack { message: "Frame", source })?;
+
+    Ok(TelemetryFrame {
+        identifier: state.id.clone(),
+        kind: FrameKind::Location,
+        timestamp: now,
+        payload: payload.to_vec(),
+    })
+}
 
-    if result.status() != StatusCode::OK {
-        println!(
-            "({}) could not issue position update: {}",
-            state.id,
-            result.status()
-        );
-        return Err(NetworkError::Unauthorized);
+/// Unpack a NETRID frame and render the message it carries
+pub fn decode_frame(bytes: &[u8]) -> Result<(FrameKind, String), String> {
+    let frame =
+        Frame::unpack_from_slice(bytes).map_err(|e| format!("could not unpack frame: {:?}", e))?;
+
+    match frame.header.message_type {
+        MessageType::Basic => BasicMessage::unpack_from_slice(&frame.message)
+            .map(|m| (FrameKind::Basic, format!("{:?}", m)))
+            .map_err(|e| format!("could not unpack basic message: {:?}", e)),
+        MessageType::Location => LocationMessage::unpack_from_slice(&frame.message)
+            .map(|m| (FrameKind::Location, format!("{:?}", m)))
+            .map_err(|e| format!("could not unpack location message: {:?}", e)),
+        other => Err(format!("unsupported message type: {:?}", other)),
     }
-
-    // println!("({}) response {:#?}.", state.id, result);
-    // println!("({}) issued position update.", state.id);
-    Ok(())
 }
 
-pub(crate) fn adjust_vertical_velocity(current_ms: &u64, state: &mut State) {
-    println!("| {} | {current_ms} | adjusting velocity.", state.id);
-    println!("| {} | {current_ms} | current location: {:?}", state.id, state.position);
-    let Some(ref plan) = state.current_plan else {
+/// Ground speed below which the track follows the heading
+const GROUND_SPEED_EPSILON_M_S: f64 = 0.01;
+
+/// Steer and move the aircraft along its plan for the time since `last_ms`
+///
+/// Intermediate points are reached within the arrival radius, the last
+///  point of the plan on touchdown at the end of the landing. Landings
+///  without a plan touch down on the ground below the aircraft, at
+///  altitude 0. In the air the aircraft drifts with `wind`.
+pub fn update_location(
+    current_ms: &u64,
+    last_ms: &u64,
+    state: &mut State,
+    limits: &KinematicLimits,
+    phases: &PhaseOptions,
+    wind: &mut Wind,
+) {
+    let in_place = state.current_plan.is_none() && state.activity == Activity::Landing;
+    if state.current_plan.is_none() && !in_place {
         return;
-    };
+    }
 
-    let Some(ref next_point) = plan.path.get(0) else {
-        println!("| {} | {current_ms} | no more points in plan.", state.id);
-        return;
+    let (mean_wind, actual_wind) = if state.activity.is_airborne() {
+        (wind.mean(&state.position), wind.at(*current_ms, &state.position))
+    } else {
+        (Velocity::default(), Velocity::default())
     };
 
-    let p1 = point!(x: state.position.longitude, y: state.position.latitude);
-    let p2 = point!(x: next_point.longitude, y: next_point.latitude);
-    let distance = p1.haversine_distance(&p2); 
-    let time_to_next_point_s = distance / state.ground_velocity_m_s;
-
-    println!("| {} | {} | next point: {:?} in {} s", state.id, current_ms, next_point, time_to_next_point_s);
-    
-    state.vertical_velocity_m_s = (next_point.altitude_meters - state.position.altitude_meters) / time_to_next_point_s;
-    state.track_angle_deg = p1.haversine_bearing(p2);
-    if state.track_angle_deg < 0.0 {
-        state.track_angle_deg += 360.0;
+    let elapsed_s = (current_ms.saturating_sub(*last_ms) as f64) / 1000.0;
+    if in_place {
+        kinematics::descend(state, limits, phases, mean_wind, elapsed_s);
+    } else {
+        kinematics::steer(*current_ms, state, limits, phases, mean_wind, elapsed_s);
     }
 
-    println!("| {} | {} | adjusted velocity; hor m/s: {}, vert m/s: {}, bearing (deg): {}", state.id, current_ms, state.ground_velocity_m_s, state.vertical_velocity_m_s, state.track_angle_deg);
-}
-
-pub(crate) fn update_location(current_ms: &u64, last_ms: &u64, state: &mut State) {
-    let Some(ref mut plan) = state.current_plan else {
-        state.activity = Activity::Idle;
-        return;
+    // over the ground, the aircraft flies through the moving air
+    let ground = Velocity::towards(state.airspeed_m_s, state.heading_deg) + actual_wind;
+    state.ground_velocity_m_s = ground.speed_m_s();
+    state.track_angle_deg = if state.ground_velocity_m_s > GROUND_SPEED_EPSILON_M_S {
+        ground.direction_deg()
+    } else {
+        state.heading_deg
     };
 
     // update state
-    let elapsed_s = ((current_ms - last_ms) as f64) / 1000.0;
     let vertical_travel_distance_m = state.vertical_velocity_m_s * elapsed_s;
     let horizontal_travel_distance_m = state.ground_velocity_m_s * elapsed_s;
     state.position.altitude_meters += vertical_travel_distance_m;
@@ -263,20 +227,43 @@ pub(crate) fn update_location(current_ms: &u64, last_ms: &u64, state: &mut State
     state.position.longitude = p2.x();
     state.position.latitude = p2.y();
 
-    let Some(ref next_point) = plan.path.get(0) else {
-        println!("| {} | {current_ms} | no more points in plan.", state.id);
+    let arrival_radius_m = kinematics::arrival_radius_m(state, limits);
+    let landing = state.activity == Activity::Landing;
+    let Some(ref mut plan) = state.current_plan else {
+        if state.position.altitude_meters <= phase::TOUCHDOWN_M {
+            tracing::info!(current_ms, "touched down in place");
+            state.position.altitude_meters = 0.0;
+            state.stop();
+            let next = phase::after_landing(state, phases);
+            phase::transition(state, next, *current_ms);
+        }
+        return;
+    };
+
+    let Some(next_point) = plan.path.first() else {
+        tracing::trace!(current_ms, "no more points in plan");
         return;
     };
 
+    if plan.path.len() == 1 {
+        let ground_m = next_point.altitude_meters;
+        if !landing || state.position.altitude_meters > ground_m + phase::TOUCHDOWN_M {
+            return;
+        }
+
+        tracing::info!(current_ms, "touched down at target");
+        plan.path.remove(0);
+        state.position.altitude_meters = ground_m;
+        state.stop();
+        return;
+    }
+
     let p3 = point!(x: next_point.longitude, y: next_point.latitude);
-    // println!("| {} | {} | longitude: {}, latitude: {}, altitude: {}", state.id, current_ms, state.position.longitude, state.position.latitude, state.position.altitude_meters);
-    if p2.haversine_distance(&p3) >= 5.0 {
+    if p2.haversine_distance(&p3) >= arrival_radius_m {
         return;
     }
 
     // Arrived at point
-    println!("| {} | {} | arrived at intermediate point.", state.id, current_ms);
-    plan.path.remove(0);
-
-    adjust_vertical_velocity(current_ms, state);
+    tracing::info!(current_ms, remaining = plan.path.len() - 1, "arrived at intermediate point");
+    state.leg_origin = Some(plan.path.remove(0));
 }
@@ -0,0 +1,91 @@
+//! Fixtures shared by the unit tests
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use svc_atc_client_rest::types::{FlightPlan, PointZ};
+
+use crate::backend::MemoryBackend;
+use crate::clock::SimClock;
+use crate::fleet::AircraftStatus;
+use crate::scenario::Rates;
+use crate::{Activity, Aircraft, AircraftConfig, State};
+
+pub const ORIGIN: (f64, f64) = (52.3676, 4.9041);
+pub const WAYPOINT: (f64, f64) = (52.3721, 4.9041);
+pub const DESTINATION: (f64, f64) = (52.3721, 4.9115);
+
+/// A plan departing one second after `start` and arriving two minutes later
+pub fn flight_plan(start: DateTime<Utc>) -> FlightPlan {
+    let point = |(latitude, longitude): (f64, f64), altitude_meters: f64| {
+        json!({ "latitude": latitude, "longitude": longitude, "altitude_meters": altitude_meters })
+    };
+
+    let parcel = |id: &str| json!({ "id": id, "weight_g": 1000 });
+
+    serde_json::from_value(json!({
+        "flight_uuid": "00000000-0000-0000-0000-00000000f001",
+        "session_id": "AETH0001",
+        "aircraft_id": "00000000-0000-0000-0000-00000000a001",
+        "origin_vertiport_id": "00000000-0000-0000-0000-00000000b001",
+        "origin_vertipad_id": "00000000-0000-0000-0000-00000000c001",
+        "target_vertiport_id": "00000000-0000-0000-0000-00000000b002",
+        "target_vertipad_id": "00000000-0000-0000-0000-00000000c002",
+        "path": [
+            point(ORIGIN, 0.0),
+            point(WAYPOINT, 50.0),
+            point(DESTINATION, 0.0),
+        ],
+        "origin_timeslot_start": start,
+        "origin_timeslot_end": start + Duration::seconds(1),
+        "target_timeslot_start": start + Duration::seconds(121),
+        "target_timeslot_end": start + Duration::seconds(151),
+        "acquire": [parcel("parcel-1"), parcel("parcel-2")],
+        "deliver": [parcel("parcel-1")],
+    }))
+    .expect("flight plan fixture should match FlightPlan")
+}
+
+/// An idle aircraft at the origin of [`flight_plan`]
+pub fn state() -> State {
+    State::new(
+        "Mantis".to_string(),
+        "scanner".to_string(),
+        PointZ {
+            latitude: ORIGIN.0,
+            longitude: ORIGIN.1,
+            altitude_meters: 0.0,
+        },
+    )
+}
+
+/// Mantis, parked at the origin of [`flight_plan`]
+pub fn config() -> AircraftConfig {
+    AircraftConfig {
+        name: "Mantis".to_string(),
+        uuid: "uuid".to_string(),
+        scanner_id: "scanner".to_string(),
+        longitude: ORIGIN.1,
+        latitude: ORIGIN.0,
+        serial_number: None,
+        profile: None,
+    }
+}
+
+/// Status of Mantis as it would be published with `activity`
+pub fn aircraft_status(activity: Activity) -> AircraftStatus {
+    let backend = MemoryBackend::new();
+    let clock = SimClock::stepped(Utc::now(), 50);
+    let aircraft = Aircraft::new(
+        config(),
+        clock,
+        Rates::default(),
+        backend.clone(),
+        backend.clone(),
+        backend,
+    );
+
+    AircraftStatus {
+        activity,
+        ..aircraft.status()
+    }
+}
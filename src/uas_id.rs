@@ -0,0 +1,194 @@
+//! UAS IDs carried by NETRID Basic ID messages
+//!
+//! The UAS ID field is 20 bytes. Session IDs that do not fit, like the
+//!  UUIDs handed out by svc-atc, are sent as a derived session ID. Serial
+//!  numbers must be valid ANSI/CTA-2063-A serial numbers.
+
+use sha2::{Digest, Sha256};
+use svc_telemetry_client_rest::netrid_types::IdType;
+
+use crate::error::EncodeError;
+
+/// Length of the UAS ID field of a Basic ID message
+pub const UAS_ID_LEN: usize = 20;
+
+/// Length of the manufacturer code of a CTA-2063-A serial number
+const MANUFACTURER_CODE_LEN: usize = 4;
+
+/// Why a serial number is not a valid CTA-2063-A serial number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerialError {
+    /// shorter than a manufacturer code, a length code and one character
+    TooShort,
+    /// the length code is not `1`-`9` or `A`-`F`
+    LengthCode(char),
+    /// the manufacturer serial is not as long as the length code says
+    Length { expected: usize, actual: usize },
+    /// only digits and upper case letters other than `O` and `I` are allowed
+    Character(char),
+}
+
+impl std::fmt::Display for SerialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerialError::TooShort => write!(f, "serial number is too short"),
+            SerialError::LengthCode(c) => write!(f, "invalid length code {c:?}"),
+            SerialError::Length { expected, actual } => write!(
+                f,
+                "length code announces {expected} characters, found {actual}"
+            ),
+            SerialError::Character(c) => write!(f, "invalid character {c:?}"),
+        }
+    }
+}
+
+impl std::error::Error for SerialError {}
+
+/// If `c` may appear in a CTA-2063-A serial number
+fn is_serial_char(c: char) -> bool {
+    c.is_ascii_digit() || (c.is_ascii_uppercase() && c != 'O' && c != 'I')
+}
+
+/// Validate an ANSI/CTA-2063-A serial number
+///
+/// A serial number is a 4 character manufacturer code, a length code `1`-`9`
+///  or `A`-`F`, and a manufacturer serial of that many characters, e.g.
+///  `MFR1C123456789ABC`.
+pub fn validate_serial(serial: &str) -> Result<(), SerialError> {
+    if let Some(c) = serial.chars().find(|c| !is_serial_char(*c)) {
+        return Err(SerialError::Character(c));
+    }
+
+    // only ASCII from here on
+    if serial.len() < MANUFACTURER_CODE_LEN + 2 {
+        return Err(SerialError::TooShort);
+    }
+
+    let length_code = serial.as_bytes()[MANUFACTURER_CODE_LEN] as char;
+    let expected = match length_code.to_digit(16) {
+        Some(n) if n > 0 => n as usize,
+        _ => return Err(SerialError::LengthCode(length_code)),
+    };
+
+    let actual = serial.len() - MANUFACTURER_CODE_LEN - 1;
+    if actual != expected {
+        return Err(SerialError::Length { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Session ID sent for `session`
+///
+/// Sessions that fit the UAS ID field are sent as is. Longer ones are sent
+///  as the upper case hex of the first 10 bytes of their SHA-256, which is
+///  stable across runs and aircraft.
+pub fn session_id(session: &str) -> String {
+    if session.len() <= UAS_ID_LEN {
+        return session.to_string();
+    }
+
+    let digest = Sha256::digest(session.as_bytes());
+    digest[..UAS_ID_LEN / 2]
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect()
+}
+
+/// Encode `uas_id` into the UAS ID field of a Basic ID message of `id_type`
+///
+/// The ID is right aligned and padded with spaces.
+pub fn encode(id_type: IdType, uas_id: &str) -> Result<[u8; UAS_ID_LEN], EncodeError> {
+    let id = match id_type {
+        IdType::SpecificSession => session_id(uas_id),
+        IdType::SerialNumber => {
+            validate_serial(uas_id).map_err(EncodeError::Serial)?;
+            uas_id.to_string()
+        }
+        _ => uas_id.to_string(),
+    };
+
+    let bytes = id.as_bytes();
+    if bytes.len() > UAS_ID_LEN {
+        return Err(EncodeError::Identifier(uas_id.to_string()));
+    }
+
+    let mut field = [b' '; UAS_ID_LEN];
+    field[UAS_ID_LEN - bytes.len()..].copy_from_slice(bytes);
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_serials() {
+        for serial in ["MFR1C123456789ABC", "ABCD1X", "1234F123456789ABCDEF"] {
+            assert_eq!(validate_serial(serial), Ok(()), "{serial}");
+        }
+    }
+
+    #[test]
+    fn rejects_serials_of_the_wrong_length() {
+        assert_eq!(validate_serial(""), Err(SerialError::TooShort));
+        assert_eq!(validate_serial("MFR1C"), Err(SerialError::TooShort));
+        assert_eq!(
+            validate_serial("MFR1C123"),
+            Err(SerialError::Length {
+                expected: 12,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            validate_serial("MFR12345"),
+            Err(SerialError::Length {
+                expected: 2,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_length_codes() {
+        assert_eq!(validate_serial("MFR10123"), Err(SerialError::LengthCode('0')));
+        assert_eq!(validate_serial("MFR1G123"), Err(SerialError::LengthCode('G')));
+    }
+
+    #[test]
+    fn rejects_invalid_characters() {
+        // in the manufacturer code
+        assert_eq!(validate_serial("MFO11X"), Err(SerialError::Character('O')));
+        assert_eq!(validate_serial("mfr11X"), Err(SerialError::Character('m')));
+        // in the manufacturer serial
+        assert_eq!(validate_serial("MFR13AI1"), Err(SerialError::Character('I')));
+        assert_eq!(validate_serial("MFR13A-1"), Err(SerialError::Character('-')));
+        assert_eq!(validate_serial("MFR13Aé1"), Err(SerialError::Character('é')));
+    }
+
+    #[test]
+    fn derives_session_ids_that_do_not_fit() {
+        assert_eq!(session_id("AETH0001"), "AETH0001");
+        assert_eq!(
+            session_id("00000000-0000-0000-0000-00000000d001"),
+            "EB34CED17F8F1BAB12FD"
+        );
+    }
+
+    #[test]
+    fn pads_ids_to_the_right() {
+        let field = encode(IdType::SpecificSession, "AETH0001").unwrap();
+        assert_eq!(&field, b"            AETH0001");
+
+        let field = encode(IdType::SerialNumber, "MFR1C123456789ABC").unwrap();
+        assert_eq!(&field, b"   MFR1C123456789ABC");
+        assert!(matches!(
+            encode(IdType::SerialNumber, "MFR1C123"),
+            Err(EncodeError::Serial(SerialError::Length { .. }))
+        ));
+        assert!(matches!(
+            encode(IdType::CaaAssigned, "CAA-ASSIGNED-ID-TOO-LONG"),
+            Err(EncodeError::Identifier(_))
+        ));
+    }
+}
@@ -0,0 +1,549 @@
+//! Wind drifting the aircraft off its heading
+//!
+//! The wind is the same everywhere, layered by altitude or interpolated
+//!  from a gridded JSON file, with random gusts on top. Aircraft fly
+//!  through the air: the guidance in [`crate::kinematics::steer`] crabs
+//!  into the mean wind to hold the ground track of the leg, gusts drift the
+//!  aircraft off it until the guidance corrects.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::sync::Arc;
+use svc_atc_client_rest::types::PointZ;
+
+/// Where the wind comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindMode {
+    #[default]
+    Calm,
+    /// `speed_m_s` from `from_deg` everywhere
+    Constant,
+    /// interpolated between `layers` by altitude
+    Layered,
+    /// interpolated from the `grid` file by position and altitude
+    Grid,
+}
+
+/// Wind at one altitude
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WindLayer {
+    pub altitude_m: f64,
+    pub speed_m_s: f64,
+    /// direction the wind blows from, clockwise from north
+    pub from_deg: f64,
+}
+
+/// Wind of the scenario
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WindOptions {
+    pub mode: WindMode,
+    pub speed_m_s: f64,
+    /// direction the wind blows from, clockwise from north
+    pub from_deg: f64,
+    pub layers: Vec<WindLayer>,
+    /// path of the gridded wind file
+    pub grid: Option<String>,
+    /// strongest gust, 0 for none
+    pub gust_m_s: f64,
+    /// time between gusts; the wind shifts gradually from one to the next
+    pub gust_period_ms: u64,
+    /// seeds the gusts, each aircraft gets its own sequence
+    pub seed: u64,
+}
+
+impl Default for WindOptions {
+    fn default() -> Self {
+        WindOptions {
+            mode: WindMode::Calm,
+            speed_m_s: 0.0,
+            from_deg: 0.0,
+            layers: vec![],
+            grid: None,
+            gust_m_s: 0.0,
+            gust_period_ms: 3000,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WindError {
+    Io(String, std::io::Error),
+    Parse(String, serde_json::Error),
+    Invalid(&'static str),
+}
+
+impl std::fmt::Display for WindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindError::Io(path, e) => write!(f, "could not read wind grid {}: {}", path, e),
+            WindError::Parse(path, e) => write!(f, "could not parse wind grid {}: {}", path, e),
+            WindError::Invalid(reason) => write!(f, "invalid wind: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for WindError {}
+
+/// Horizontal velocity
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Velocity {
+    pub east_m_s: f64,
+    pub north_m_s: f64,
+}
+
+impl Velocity {
+    /// `speed_m_s` towards `direction_deg`, clockwise from north
+    pub fn towards(speed_m_s: f64, direction_deg: f64) -> Self {
+        let (sin, cos) = direction_deg.to_radians().sin_cos();
+        Velocity {
+            east_m_s: speed_m_s * sin,
+            north_m_s: speed_m_s * cos,
+        }
+    }
+
+    /// Wind of `speed_m_s` blowing from `from_deg`
+    pub fn from_wind(speed_m_s: f64, from_deg: f64) -> Self {
+        Velocity::towards(speed_m_s, from_deg + 180.0)
+    }
+
+    pub fn speed_m_s(&self) -> f64 {
+        self.east_m_s.hypot(self.north_m_s)
+    }
+
+    /// Direction of travel, clockwise from north in [0, 360)
+    pub fn direction_deg(&self) -> f64 {
+        self.east_m_s.atan2(self.north_m_s).to_degrees().rem_euclid(360.0)
+    }
+
+    fn scale(self, factor: f64) -> Velocity {
+        Velocity {
+            east_m_s: self.east_m_s * factor,
+            north_m_s: self.north_m_s * factor,
+        }
+    }
+
+    /// Linear interpolation, `t` = 0 is `self`
+    fn lerp(self, other: Velocity, t: f64) -> Velocity {
+        self + (other - self).scale(t)
+    }
+}
+
+impl std::ops::Add for Velocity {
+    type Output = Velocity;
+
+    fn add(self, other: Velocity) -> Velocity {
+        Velocity {
+            east_m_s: self.east_m_s + other.east_m_s,
+            north_m_s: self.north_m_s + other.north_m_s,
+        }
+    }
+}
+
+impl std::ops::Sub for Velocity {
+    type Output = Velocity;
+
+    fn sub(self, other: Velocity) -> Velocity {
+        self + other.scale(-1.0)
+    }
+}
+
+/// Contents of a gridded wind file
+///
+/// Each layer holds the wind components at every grid point, one row per
+///  latitude and one column per longitude.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindGrid {
+    /// ascending
+    pub latitudes: Vec<f64>,
+    /// ascending
+    pub longitudes: Vec<f64>,
+    /// ascending by altitude
+    pub layers: Vec<GridLayer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GridLayer {
+    pub altitude_m: f64,
+    pub east_m_s: Vec<Vec<f64>>,
+    pub north_m_s: Vec<Vec<f64>>,
+}
+
+impl WindGrid {
+    fn validate(&self) -> Result<(), WindError> {
+        if self.latitudes.is_empty() || self.longitudes.is_empty() || self.layers.is_empty() {
+            return Err(WindError::Invalid("wind grid without points"));
+        }
+
+        let ascending = |values: &[f64]| values.windows(2).all(|w| w[0] < w[1]);
+        let altitudes: Vec<f64> = self.layers.iter().map(|l| l.altitude_m).collect();
+        if !ascending(&self.latitudes) || !ascending(&self.longitudes) || !ascending(&altitudes) {
+            return Err(WindError::Invalid("wind grid axes must be ascending"));
+        }
+
+        let shaped = |rows: &Vec<Vec<f64>>| {
+            rows.len() == self.latitudes.len()
+                && rows.iter().all(|row| row.len() == self.longitudes.len())
+        };
+        if !self.layers.iter().all(|l| shaped(&l.east_m_s) && shaped(&l.north_m_s)) {
+            return Err(WindError::Invalid("wind grid layers must match the axes"));
+        }
+
+        Ok(())
+    }
+
+    fn velocity(&self, position: &PointZ) -> Velocity {
+        let (lat, lat_t) = bracket(&self.latitudes, position.latitude);
+        let (lon, lon_t) = bracket(&self.longitudes, position.longitude);
+
+        // bilinear within a layer
+        let at = |layer: &GridLayer| {
+            let corner = |i: usize, j: usize| Velocity {
+                east_m_s: layer.east_m_s[i][j],
+                north_m_s: layer.north_m_s[i][j],
+            };
+
+            let south = corner(lat.0, lon.0).lerp(corner(lat.0, lon.1), lon_t);
+            let north = corner(lat.1, lon.0).lerp(corner(lat.1, lon.1), lon_t);
+            south.lerp(north, lat_t)
+        };
+
+        let altitudes: Vec<f64> = self.layers.iter().map(|l| l.altitude_m).collect();
+        let (layer, t) = bracket(&altitudes, position.altitude_meters);
+        at(&self.layers[layer.0]).lerp(at(&self.layers[layer.1]), t)
+    }
+}
+
+/// Indices of the values around `x` in ascending `values`, and where `x`
+///  lies between them; clamped to the ends
+fn bracket(values: &[f64], x: f64) -> ((usize, usize), f64) {
+    let last = values.len() - 1;
+    if x <= values[0] {
+        return ((0, 0), 0.0);
+    }
+    if x >= values[last] {
+        return ((last, last), 0.0);
+    }
+
+    let upper = values.partition_point(|v| *v <= x);
+    let lower = upper - 1;
+    let t = (x - values[lower]) / (values[upper] - values[lower]);
+    ((lower, upper), t)
+}
+
+/// Mean wind, without gusts
+#[derive(Debug, Clone)]
+enum Field {
+    Calm,
+    Constant(Velocity),
+    /// ascending by altitude
+    Layered(Vec<(f64, Velocity)>),
+    Grid(WindGrid),
+}
+
+/// Wind of the scenario, shared by the fleet
+#[derive(Debug, Clone)]
+pub struct WindModel {
+    field: Arc<Field>,
+    gust_m_s: f64,
+    gust_period_ms: u64,
+    seed: u64,
+}
+
+impl Default for WindModel {
+    fn default() -> Self {
+        WindModel {
+            field: Arc::new(Field::Calm),
+            gust_m_s: 0.0,
+            gust_period_ms: 1,
+            seed: 0,
+        }
+    }
+}
+
+impl WindModel {
+    /// Build the wind described by `options`, reading the grid file if
+    ///  there is one
+    pub fn load(options: &WindOptions) -> Result<WindModel, WindError> {
+        if options.gust_period_ms == 0 {
+            return Err(WindError::Invalid("gust_period_ms must be greater than zero"));
+        }
+
+        let field = match options.mode {
+            WindMode::Calm => Field::Calm,
+            WindMode::Constant => {
+                Field::Constant(Velocity::from_wind(options.speed_m_s, options.from_deg))
+            }
+            WindMode::Layered => {
+                if options.layers.is_empty() {
+                    return Err(WindError::Invalid("layered wind without layers"));
+                }
+
+                let mut layers: Vec<(f64, Velocity)> = options
+                    .layers
+                    .iter()
+                    .map(|l| (l.altitude_m, Velocity::from_wind(l.speed_m_s, l.from_deg)))
+                    .collect();
+                layers.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Field::Layered(layers)
+            }
+            WindMode::Grid => {
+                let Some(ref path) = options.grid else {
+                    return Err(WindError::Invalid("grid wind without a grid file"));
+                };
+
+                let contents =
+                    std::fs::read_to_string(path).map_err(|e| WindError::Io(path.clone(), e))?;
+                let grid: WindGrid = serde_json::from_str(&contents)
+                    .map_err(|e| WindError::Parse(path.clone(), e))?;
+                grid.validate()?;
+                Field::Grid(grid)
+            }
+        };
+
+        Ok(WindModel {
+            field: Arc::new(field),
+            gust_m_s: options.gust_m_s.max(0.0),
+            gust_period_ms: options.gust_period_ms,
+            seed: options.seed,
+        })
+    }
+
+    /// Wind as felt by the aircraft `name`, with its own gusts
+    pub fn for_aircraft(&self, name: &str) -> Wind {
+        // FNV-1a, stable across runs and platforms
+        let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+        Wind {
+            model: self.clone(),
+            rng: StdRng::seed_from_u64(self.seed ^ hash),
+            gust: None,
+        }
+    }
+
+    /// Mean wind at `position`
+    pub fn mean(&self, position: &PointZ) -> Velocity {
+        match *self.field {
+            Field::Calm => Velocity::default(),
+            Field::Constant(velocity) => velocity,
+            Field::Layered(ref layers) => {
+                let altitudes: Vec<f64> = layers.iter().map(|l| l.0).collect();
+                let ((lower, upper), t) = bracket(&altitudes, position.altitude_meters);
+                layers[lower].1.lerp(layers[upper].1, t)
+            }
+            Field::Grid(ref grid) => grid.velocity(position),
+        }
+    }
+}
+
+/// Gust blowing between two random samples
+#[derive(Debug, Clone, Copy)]
+struct Gust {
+    from: Velocity,
+    to: Velocity,
+    since_ms: u64,
+}
+
+/// Wind felt by a single aircraft
+#[derive(Debug, Clone)]
+pub struct Wind {
+    model: WindModel,
+    rng: StdRng,
+    gust: Option<Gust>,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        WindModel::default().for_aircraft("")
+    }
+}
+
+impl Wind {
+    /// Mean wind at `position`, what the guidance corrects for
+    pub fn mean(&self, position: &PointZ) -> Velocity {
+        self.model.mean(position)
+    }
+
+    /// Wind at `position` at `now_ms`, gusts included
+    pub fn at(&mut self, now_ms: u64, position: &PointZ) -> Velocity {
+        let mean = self.mean(position);
+        if self.model.gust_m_s <= 0.0 {
+            return mean;
+        }
+
+        let period_ms = self.model.gust_period_ms;
+        let mut gust = match self.gust {
+            Some(gust) => gust,
+            None => Gust {
+                from: Velocity::default(),
+                to: self.sample_gust(),
+                since_ms: now_ms,
+            },
+        };
+
+        // shift towards a new gust once the current one is reached; after
+        //  more than a period without wind, e.g. on the ground, the next
+        //  gust starts now instead of replaying the ones missed
+        let elapsed_ms = now_ms.saturating_sub(gust.since_ms);
+        if elapsed_ms >= period_ms {
+            let since_ms = if elapsed_ms < period_ms.saturating_mul(2) {
+                gust.since_ms + period_ms
+            } else {
+                now_ms
+            };
+
+            gust = Gust {
+                from: gust.to,
+                to: self.sample_gust(),
+                since_ms,
+            };
+        }
+        self.gust = Some(gust);
+
+        let t = now_ms.saturating_sub(gust.since_ms) as f64 / period_ms as f64;
+        mean + gust.from.lerp(gust.to, t)
+    }
+
+    fn sample_gust(&mut self) -> Velocity {
+        let speed = self.rng.gen_range(0.0..=self.model.gust_m_s);
+        let direction = self.rng.gen_range(0.0..360.0);
+        Velocity::towards(speed, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_altitude(altitude_meters: f64) -> PointZ {
+        PointZ {
+            latitude: 52.37,
+            longitude: 4.90,
+            altitude_meters,
+        }
+    }
+
+    fn gusty(period_ms: u64) -> WindModel {
+        WindModel::load(&WindOptions {
+            mode: WindMode::Constant,
+            speed_m_s: 5.0,
+            from_deg: 270.0,
+            gust_m_s: 2.0,
+            gust_period_ms: period_ms,
+            seed: 7,
+            ..Default::default()
+        })
+        .expect("wind should load")
+    }
+
+    #[test]
+    fn blows_from_the_given_direction() {
+        let west = Velocity::from_wind(5.0, 270.0);
+        assert!((west.east_m_s - 5.0).abs() < 1e-9);
+        assert!(west.north_m_s.abs() < 1e-9);
+        assert!((west.direction_deg() - 90.0).abs() < 1e-9);
+        assert!((west.speed_m_s() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn interpolates_layers_by_altitude() {
+        let wind = WindModel::load(&WindOptions {
+            mode: WindMode::Layered,
+            layers: vec![
+                WindLayer {
+                    altitude_m: 100.0,
+                    speed_m_s: 10.0,
+                    from_deg: 180.0,
+                },
+                WindLayer {
+                    altitude_m: 0.0,
+                    speed_m_s: 2.0,
+                    from_deg: 180.0,
+                },
+            ],
+            ..Default::default()
+        })
+        .expect("wind should load");
+
+        assert!((wind.mean(&at_altitude(-10.0)).north_m_s - 2.0).abs() < 1e-9);
+        assert!((wind.mean(&at_altitude(50.0)).north_m_s - 6.0).abs() < 1e-9);
+        assert!((wind.mean(&at_altitude(500.0)).north_m_s - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let layered = WindOptions {
+            mode: WindMode::Layered,
+            ..Default::default()
+        };
+        assert!(matches!(WindModel::load(&layered), Err(WindError::Invalid(_))));
+
+        let grid = WindOptions {
+            mode: WindMode::Grid,
+            ..Default::default()
+        };
+        assert!(matches!(WindModel::load(&grid), Err(WindError::Invalid(_))));
+
+        let no_period = WindOptions {
+            gust_period_ms: 0,
+            ..Default::default()
+        };
+        assert!(matches!(WindModel::load(&no_period), Err(WindError::Invalid(_))));
+    }
+
+    #[test]
+    fn gusts_are_bounded_and_reproducible() {
+        let model = gusty(1000);
+        let mean = model.mean(&at_altitude(30.0));
+        let mut first = model.for_aircraft("Mantis");
+        let mut second = model.for_aircraft("Mantis");
+        let mut other = model.for_aircraft("Hornet");
+
+        let mut differs = false;
+        for t in (0..20_000).step_by(250) {
+            let wind = first.at(t, &at_altitude(30.0));
+            assert_eq!(wind, second.at(t, &at_altitude(30.0)));
+            assert!((wind - mean).speed_m_s() <= 2.0 + 1e-9);
+            differs |= wind != other.at(t, &at_altitude(30.0));
+        }
+
+        assert!(differs, "every aircraft should have gusts of its own");
+    }
+
+    #[test]
+    fn shifts_gradually_between_gusts() {
+        let mut wind = gusty(1000).for_aircraft("Mantis");
+        let position = at_altitude(30.0);
+        let start = wind.at(0, &position);
+        let halfway = wind.at(500, &position);
+        let next = wind.at(1000, &position);
+        let gust = wind.gust.expect("gusting");
+
+        // the first gust rises from calm
+        assert_eq!(start, wind.mean(&position));
+        let expected = start.lerp(next, 0.5);
+        assert!((halfway - expected).speed_m_s() < 1e-9);
+        assert_eq!(gust.since_ms, 1000);
+    }
+
+    #[test]
+    fn starts_a_new_gust_after_a_long_pause() {
+        let mut wind = gusty(1).for_aircraft("Mantis");
+        let position = at_altitude(30.0);
+        wind.at(0, &position);
+
+        // one sample, not one per period missed
+        let now_ms = 1_000_000_000_000;
+        wind.at(now_ms, &position);
+        let gust = wind.gust.expect("gusting");
+        assert_eq!(gust.since_ms, now_ms);
+
+        wind.at(now_ms + 1, &position);
+        assert_eq!(wind.gust.expect("gusting").since_ms, now_ms + 1);
+    }
+}
@@ -0,0 +1,774 @@
+//! Flies a known flight plan against in-process stand-in backends
+
+use chrono::{DateTime, Duration, Utc};
+use geo::prelude::*;
+use geo::point;
+use hyper::client::{connect::HttpConnector, Client};
+use serde_json::json;
+use std::net::SocketAddr;
+use svc_atc_client_rest::types::FlightPlan;
+use tokio::sync::{mpsc, watch};
+
+use sim_carrier::backend::http::{Endpoints, HttpBackend};
+use sim_carrier::backend::memory::ScanRecord;
+use sim_carrier::backend::{CargoScanner, Guarded, MemoryBackend, OrderSource, TelemetrySink};
+use sim_carrier::battery::{Battery, BatteryOptions, ChargeLevel};
+use sim_carrier::checkpoint;
+use sim_carrier::clock::SimClock;
+use sim_carrier::control::Command;
+use sim_carrier::error::BackendError;
+use sim_carrier::fleet::{AircraftStatus, StatusBoard};
+use sim_carrier::link::LinkOptions;
+use sim_carrier::mock::MockServer;
+use sim_carrier::profile::{Airframe, PerformanceProfile};
+use sim_carrier::retry::{Breakers, RetryPolicy};
+use sim_carrier::scenario::Rates;
+use sim_carrier::shutdown::{Landing, Shutdown, ShutdownMode, ShutdownOptions};
+use sim_carrier::telemetry::{decode_frame, FrameKind, TelemetryFrame};
+use sim_carrier::wind::{WindMode, WindModel, WindOptions};
+use sim_carrier::{Activity, Aircraft, AircraftConfig, State};
+
+const TICK_MS: u64 = 50;
+const ORIGIN: (f64, f64) = (52.3676, 4.9041);
+const WAYPOINT: (f64, f64) = (52.3721, 4.9041);
+const DESTINATION: (f64, f64) = (52.3721, 4.9115);
+const FLIGHT_UUID: &str = "00000000-0000-0000-0000-00000000f001";
+const QUEUED_FLIGHT_UUID: &str = "00000000-0000-0000-0000-00000000f002";
+
+type MemoryAircraft = Aircraft<MemoryBackend, MemoryBackend, MemoryBackend>;
+
+/// A plan departing one second after `start` and arriving two minutes later
+fn flight_plan(start: DateTime<Utc>) -> FlightPlan {
+    let point = |(latitude, longitude): (f64, f64), altitude_meters: f64| {
+        json!({ "latitude": latitude, "longitude": longitude, "altitude_meters": altitude_meters })
+    };
+
+    let parcel = |id: &str| json!({ "id": id, "weight_g": 1000 });
+
+    serde_json::from_value(json!({
+        "flight_uuid": FLIGHT_UUID,
+        "session_id": "AETH0001",
+        "aircraft_id": "00000000-0000-0000-0000-00000000a001",
+        "origin_vertiport_id": "00000000-0000-0000-0000-00000000b001",
+        "origin_vertipad_id": "00000000-0000-0000-0000-00000000c001",
+        "target_vertiport_id": "00000000-0000-0000-0000-00000000b002",
+        "target_vertipad_id": "00000000-0000-0000-0000-00000000c002",
+        "path": [
+            point(ORIGIN, 0.0),
+            point(WAYPOINT, 50.0),
+            point(DESTINATION, 0.0),
+        ],
+        "origin_timeslot_start": start,
+        "origin_timeslot_end": start + Duration::seconds(1),
+        "target_timeslot_start": start + Duration::seconds(121),
+        "target_timeslot_end": start + Duration::seconds(151),
+        "acquire": [parcel("parcel-1"), parcel("parcel-2")],
+        "deliver": [parcel("parcel-1")],
+    }))
+    .expect("flight plan fixture should match FlightPlan")
+}
+
+fn config() -> AircraftConfig {
+    AircraftConfig {
+        name: "Mantis".to_string(),
+        uuid: "00000000-0000-0000-0000-00000000a001".to_string(),
+        scanner_id: "00000000-0000-0000-0000-00000000d001".to_string(),
+        latitude: ORIGIN.0,
+        longitude: ORIGIN.1,
+        serial_number: None,
+        profile: None,
+    }
+}
+
+/// Mantis at the origin on `clock`, with `backend` for every service
+fn aircraft<B>(clock: &SimClock, backend: &B) -> Aircraft<B, B, B>
+where
+    B: TelemetrySink + OrderSource + CargoScanner + Clone,
+{
+    Aircraft::new(
+        config(),
+        clock.clone(),
+        Rates::default(),
+        backend.clone(),
+        backend.clone(),
+        backend.clone(),
+    )
+}
+
+/// Mantis with `plans` to fly from memory, on a stepped clock from `start`
+fn scenario(start: DateTime<Utc>, plans: Vec<FlightPlan>) -> (MemoryBackend, MemoryAircraft) {
+    let backend = MemoryBackend::with_plans(plans);
+    let aircraft = aircraft(&SimClock::stepped(start, TICK_MS), &backend);
+    (backend, aircraft)
+}
+
+fn distance_m((latitude, longitude): (f64, f64), scan: &ScanRecord) -> f64 {
+    point!(x: longitude, y: latitude).haversine_distance(&point!(x: scan.longitude, y: scan.latitude))
+}
+
+/// Step the aircraft until it took a flight plan and returned to idle
+async fn fly<T, O, C>(aircraft: &mut Aircraft<T, O, C>, max_steps: usize)
+where
+    T: TelemetrySink,
+    O: OrderSource,
+    C: CargoScanner,
+{
+    let mut departed = false;
+    for _ in 0..max_steps {
+        aircraft.advance();
+        aircraft.step().await;
+
+        departed |= aircraft.state.current_plan.is_some();
+        if departed && aircraft.state.current_plan.is_none() {
+            return;
+        }
+    }
+
+    panic!("aircraft did not complete the flight plan in {max_steps} steps");
+}
+
+/// Step the aircraft until `done` holds for its state
+async fn step_until<T, O, C>(
+    aircraft: &mut Aircraft<T, O, C>,
+    max_steps: usize,
+    done: impl Fn(&State) -> bool,
+) where
+    T: TelemetrySink,
+    O: OrderSource,
+    C: CargoScanner,
+{
+    for _ in 0..max_steps {
+        aircraft.advance();
+        aircraft.step().await;
+
+        if done(&aircraft.state) {
+            return;
+        }
+    }
+
+    panic!("aircraft did not get there in {max_steps} steps");
+}
+
+/// Climbing out, clear of the takeoff
+fn climbed_out(state: &State) -> bool {
+    state.activity == Activity::Climb && state.position.altitude_meters > 20.0
+}
+
+/// Number of frames expected over `duration_ms` when sent every `interval_ms`
+fn expected_frames(duration_ms: i64, interval_ms: u64) -> std::ops::RangeInclusive<usize> {
+    // a frame is due once more than the interval passed, so the effective
+    //  period is one tick longer than the interval
+    let min = duration_ms as u64 / (interval_ms + TICK_MS);
+    let max = duration_ms as u64 / interval_ms + 1;
+    (min as usize)..=(max as usize)
+}
+
+#[tokio::test]
+async fn flies_plan_against_memory_backend() {
+    let start = Utc::now();
+    let (backend, mut aircraft) = scenario(start, vec![flight_plan(start)]);
+
+    fly(&mut aircraft, 10_000).await;
+
+    assert_eq!(aircraft.state.activity, Activity::Idle);
+    assert!(aircraft.plans.is_empty());
+
+    let records = backend.records();
+    assert_eq!(records.logins, vec!["Mantis".to_string()]);
+    assert_eq!(records.acknowledged, vec![FLIGHT_UUID.to_string()]);
+
+    // Basic at 0.5 Hz and Location at 2 Hz for the whole session
+    let first = records.frames.first().expect("frames were sent").timestamp;
+    let last = records.frames.last().expect("frames were sent").timestamp;
+    let duration_ms = (last - first).num_milliseconds();
+    let basic = records.frames.iter().filter(|f| f.kind == FrameKind::Basic).count();
+    let location = records.frames.iter().filter(|f| f.kind == FrameKind::Location).count();
+    assert!(expected_frames(duration_ms, Rates::default().id_interval_ms).contains(&basic));
+    assert!(expected_frames(duration_ms, Rates::default().position_interval_ms).contains(&location));
+
+    // acquire at the origin, deliver at the destination
+    let cargo: Vec<&str> = records.scans.iter().map(|s| s.cargo_id.as_str()).collect();
+    assert_eq!(cargo, vec!["parcel-1", "parcel-2", "parcel-1"]);
+    assert!(records.scans[..2].iter().all(|s| distance_m(ORIGIN, s) < 10.0));
+    assert!(distance_m(DESTINATION, &records.scans[2]) < 10.0);
+}
+
+#[tokio::test]
+async fn flies_plan_against_mock_server() {
+    let start = Utc::now();
+    let mock = MockServer::new(vec![flight_plan(start)]);
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let (addr, server) = mock.bind(&addr).expect("mock server should bind");
+    tokio::spawn(server);
+
+    let endpoints = Endpoints {
+        tlm_uri: format!("http://{addr}/telemetry"),
+        atc_uri: format!("http://{addr}/atc"),
+        cargo_uri: format!("http://{addr}/cargo"),
+    };
+
+    let client: Client<HttpConnector> = Client::builder().build_http();
+    let backend = HttpBackend::new(client, endpoints);
+    let mut aircraft = aircraft(&SimClock::stepped(start, TICK_MS), &backend);
+
+    fly(&mut aircraft, 10_000).await;
+
+    assert_eq!(aircraft.state.activity, Activity::Idle);
+
+    let records = mock.records();
+    assert!(records.rejected.is_empty(), "rejected: {:?}", records.rejected);
+    assert_eq!(records.logins, vec!["Mantis".to_string()]);
+    assert_eq!(records.acknowledged, vec![FLIGHT_UUID.to_string()]);
+    assert!(records.frames.iter().any(|f| f.kind == FrameKind::Basic));
+    assert!(records.frames.iter().any(|f| f.kind == FrameKind::Location));
+    assert!(records.frames.iter().all(|f| f.identifier == "Mantis"));
+
+    let cargo: Vec<&str> = records.scans.iter().map(|s| s.cargo_id.as_str()).collect();
+    assert_eq!(cargo, vec!["parcel-1", "parcel-2", "parcel-1"]);
+    assert!(records.scans[..2].iter().all(|s| distance_m(ORIGIN, s) < 10.0));
+    assert!(distance_m(DESTINATION, &records.scans[2]) < 10.0);
+}
+
+#[tokio::test]
+async fn lands_and_declines_queued_plans_on_shutdown() {
+    let start = Utc::now();
+    let mut queued = flight_plan(start + Duration::seconds(600));
+    queued.flight_uuid = QUEUED_FLIGHT_UUID.to_string();
+    queued.session_id = "AETH0002".to_string();
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start), queued]);
+
+    let (signal, rx) = watch::channel(false);
+    let options = ShutdownOptions {
+        mode: ShutdownMode::Teleport,
+        decline_queued: true,
+        ..Default::default()
+    };
+
+    let mut aircraft = aircraft.with_shutdown(Shutdown { signal: rx, options });
+    step_until(&mut aircraft, 10_000, |state| state.position.altitude_meters > 10.0).await;
+
+    assert!(aircraft.state.current_plan.is_some(), "aircraft should be airborne");
+    assert_eq!(aircraft.plans.len(), 1);
+
+    signal.send_replace(true);
+    let report = aircraft.run(TICK_MS, StatusBoard::default()).await;
+
+    assert_eq!(report.landing, Landing::Teleported);
+    assert_eq!(report.declined, 1);
+    assert!(report.flushed);
+    assert_eq!(report.position.altitude_meters, 0.0);
+    let landed = point!(x: report.position.longitude, y: report.position.latitude);
+    assert!(landed.haversine_distance(&point!(x: DESTINATION.1, y: DESTINATION.0)) < 10.0);
+
+    let records = backend.records();
+    assert_eq!(records.acknowledged, vec![FLIGHT_UUID.to_string(), QUEUED_FLIGHT_UUID.to_string()]);
+    assert_eq!(records.declined, vec![QUEUED_FLIGHT_UUID.to_string()]);
+
+    // the backend last saw the aircraft on the ground
+    let last = records.frames.last().expect("frames were sent");
+    let (kind, message) = decode_frame(&last.payload).expect("final frame should decode");
+    assert_eq!(kind, FrameKind::Location);
+    assert!(message.contains("Ground"), "final frame: {message}");
+
+    // teleported aircraft do not deliver their parcels
+    let cargo: Vec<&str> = records.scans.iter().map(|s| s.cargo_id.as_str()).collect();
+    assert_eq!(cargo, vec!["parcel-1", "parcel-2"]);
+}
+
+/// `status` written to a checkpoint and read back
+fn round_trip(status: AircraftStatus) -> AircraftStatus {
+    let board = StatusBoard::default();
+    board.lock().unwrap().insert("Mantis".to_string(), status);
+    let path = std::env::temp_dir().join(format!("sim-carrier-checkpoint-{}.json", std::process::id()));
+    let path = path.to_str().expect("temp path should be valid UTF-8");
+    checkpoint::save(path, &board).expect("checkpoint should be written");
+    let saved = checkpoint::load(path).expect("checkpoint should be read back");
+    let _ = std::fs::remove_file(path);
+    saved.aircraft["Mantis"].clone()
+}
+
+#[tokio::test]
+async fn resumes_plan_from_checkpoint() {
+    let start = Utc::now();
+    let clock = SimClock::stepped(start, TICK_MS);
+    let backend = MemoryBackend::with_plans(vec![flight_plan(start)]);
+    let mut first = aircraft(&clock, &backend);
+    step_until(&mut first, 10_000, |state| state.activity == Activity::Climb).await;
+
+    // a restarted simulator with nothing left to poll
+    let restarted = MemoryBackend::new();
+    let mut second = aircraft(&clock, &restarted).with_restored(round_trip(first.status()));
+    drop(first);
+
+    assert_eq!(second.state.activity, Activity::Climb);
+    assert!(second.state.position.altitude_meters >= 10.0);
+    assert_eq!(second.state.onboard, vec!["parcel-1", "parcel-2"]);
+
+    // restart again halfway along the leg to the waypoint
+    step_until(&mut second, 10_000, |state| {
+        let position = &state.position;
+        let along_leg = state.leg_origin.as_ref().map(|origin| {
+            point!(x: origin.longitude, y: origin.latitude)
+                .haversine_distance(&point!(x: position.longitude, y: position.latitude))
+        });
+        along_leg.is_some_and(|d| d > 200.0)
+    })
+    .await;
+
+    let saved = round_trip(second.status());
+    let origin = second.state.leg_origin.clone().expect("flying a leg");
+    let phase_altitude_m = second.state.phase_altitude_m;
+    drop(second);
+    let mut aircraft = aircraft(&clock, &restarted).with_restored(saved);
+
+    let restored = aircraft.state.leg_origin.as_ref().expect("leg should be restored");
+    assert_eq!((restored.latitude, restored.longitude), (origin.latitude, origin.longitude));
+    assert_eq!(aircraft.state.phase_altitude_m, phase_altitude_m);
+
+    fly(&mut aircraft, 10_000).await;
+
+    assert_eq!(aircraft.state.activity, Activity::Idle);
+    assert_eq!(aircraft.state.onboard, vec!["parcel-2"]);
+
+    // the parcels picked up before the restart are delivered after it
+    let records = restarted.records();
+    let cargo: Vec<&str> = records.scans.iter().map(|s| s.cargo_id.as_str()).collect();
+    assert_eq!(cargo, vec!["parcel-1"]);
+    assert!(distance_m(DESTINATION, &records.scans[0]) < 10.0);
+}
+
+#[tokio::test]
+async fn flies_phases_from_preflight_to_landing() {
+    let start = Utc::now();
+    let (backend, mut aircraft) = scenario(start, vec![flight_plan(start)]);
+
+    let mut phases = vec![aircraft.state.activity];
+    let mut departed = false;
+    for _ in 0..10_000 {
+        aircraft.advance();
+        aircraft.step().await;
+
+        let state = &aircraft.state;
+        if phases.last() != Some(&state.activity) {
+            phases.push(state.activity);
+        }
+
+        // rotors only lift off after the preflight checks and spool-up
+        if !state.activity.is_airborne() {
+            assert!(state.position.altitude_meters <= 0.0, "{:?} in the air", state.activity);
+        }
+
+        departed |= state.current_plan.is_some();
+        if departed && state.current_plan.is_none() {
+            break;
+        }
+    }
+
+    // one more position report from the ground
+    for _ in 0..20 {
+        aircraft.advance();
+        aircraft.step().await;
+    }
+
+    // levelling off at the waypoint may cruise for a moment
+    phases.retain(|phase| *phase != Activity::Cruise);
+    assert_eq!(
+        phases,
+        vec![
+            Activity::Idle,
+            Activity::Preflight,
+            Activity::SpoolUp,
+            Activity::Takeoff,
+            Activity::Climb,
+            Activity::Descent,
+            Activity::Approach,
+            Activity::Landing,
+            Activity::Idle,
+        ]
+    );
+    assert_eq!(aircraft.state.position.altitude_meters, 0.0);
+
+    // on the ground before takeoff and after landing, airborne in between
+    let records = backend.records();
+    let statuses: Vec<&str> = records
+        .frames
+        .iter()
+        .filter(|f| f.kind == FrameKind::Location)
+        .map(|f| {
+            let (_, message) = decode_frame(&f.payload).expect("frame should decode");
+            if message.contains("operational_status: Ground") {
+                "Ground"
+            } else if message.contains("operational_status: Airborne") {
+                "Airborne"
+            } else {
+                panic!("unexpected operational status: {message}")
+            }
+        })
+        .collect();
+    let mut changes = statuses.clone();
+    changes.dedup();
+    assert_eq!(changes, vec!["Ground", "Airborne", "Ground"]);
+
+    // delivered at the destination after landing there
+    assert_eq!(records.scans.len(), 3);
+    assert!(distance_m(DESTINATION, &records.scans[2]) < 10.0);
+}
+
+#[tokio::test]
+async fn declines_plans_the_battery_does_not_cover() {
+    let start = Utc::now();
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let mut aircraft = aircraft.with_battery(BatteryOptions::default());
+
+    // below the reserve before the plan starts
+    aircraft.state.battery = Some(Battery {
+        capacity_wh: 1000.0,
+        charge_wh: 100.0,
+        level: ChargeLevel::Reserve,
+    });
+
+    for _ in 0..200 {
+        aircraft.advance();
+        aircraft.step().await;
+    }
+
+    assert!(aircraft.state.current_plan.is_none());
+    assert!(aircraft.plans.is_empty());
+    assert_eq!(aircraft.state.activity, Activity::Idle);
+
+    let records = backend.records();
+    assert_eq!(records.acknowledged, vec![FLIGHT_UUID.to_string()]);
+    assert_eq!(records.declined, vec![FLIGHT_UUID.to_string()]);
+    assert!(records.scans.is_empty());
+}
+
+#[tokio::test]
+async fn lands_in_place_when_the_battery_reserve_is_breached() {
+    let start = Utc::now();
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let options = BatteryOptions {
+        capacity_wh: 300.0,
+        ..Default::default()
+    };
+    let mut aircraft = aircraft.with_battery(options);
+    step_until(&mut aircraft, 10_000, |state| state.activity == Activity::Climb).await;
+
+    // the flight drew more than expected, just above the reserve is left
+    let battery = aircraft.state.battery.as_mut().expect("battery is simulated");
+    assert_eq!(battery.level, ChargeLevel::Normal);
+    battery.charge_wh = 300.0 * options.reserve_pct / 100.0 + 0.01;
+
+    fly(&mut aircraft, 10_000).await;
+
+    // landed short of the destination, charging with the parcels on board
+    assert_eq!(aircraft.state.activity, Activity::Charging);
+    assert_eq!(aircraft.state.position.altitude_meters, 0.0);
+    let landed = point!(x: aircraft.state.position.longitude, y: aircraft.state.position.latitude);
+    assert!(landed.haversine_distance(&point!(x: DESTINATION.1, y: DESTINATION.0)) > 100.0);
+    assert_eq!(aircraft.state.onboard, vec!["parcel-1", "parcel-2"]);
+    assert_eq!(aircraft.state.payload_kg, 2.0);
+
+    let records = backend.records();
+    let cargo: Vec<&str> = records.scans.iter().map(|s| s.cargo_id.as_str()).collect();
+    assert_eq!(cargo, vec!["parcel-1", "parcel-2"]);
+    assert!(records.frames.iter().any(|f| {
+        let (_, message) = decode_frame(&f.payload).expect("frame should decode");
+        message.contains("operational_status: Emergency")
+    }));
+}
+
+#[tokio::test]
+async fn lands_in_place_before_the_descent_needs_the_reserve() {
+    let start = Utc::now();
+    let (_, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let options = BatteryOptions {
+        capacity_wh: 300.0,
+        ..Default::default()
+    };
+    let mut aircraft = aircraft.with_battery(options);
+    step_until(&mut aircraft, 10_000, climbed_out).await;
+
+    // well above the reserve, but short of the target and of what hovering
+    //  down takes from here
+    let reserve_wh = 300.0 * options.reserve_pct / 100.0;
+    let battery = aircraft.state.battery.as_mut().expect("battery is simulated");
+    battery.charge_wh = reserve_wh + 5.0;
+
+    aircraft.advance();
+    aircraft.step().await;
+    assert!(aircraft.state.diverted);
+    let battery = aircraft.state.battery.expect("battery is simulated");
+    assert!(battery.charge_wh > reserve_wh);
+    assert_ne!(battery.level, ChargeLevel::Reserve);
+
+    fly(&mut aircraft, 10_000).await;
+
+    assert_eq!(aircraft.state.activity, Activity::Charging);
+    assert_eq!(aircraft.state.onboard, vec!["parcel-1", "parcel-2"]);
+}
+
+#[tokio::test]
+async fn crabs_into_the_wind_to_hold_the_legs() {
+    let start = Utc::now();
+    // from the north, across the eastbound leg to the destination
+    let options = WindOptions {
+        mode: WindMode::Constant,
+        speed_m_s: 5.0,
+        from_deg: 0.0,
+        gust_m_s: 1.5,
+        seed: 7,
+        ..Default::default()
+    };
+    let wind = WindModel::load(&options).expect("wind should load");
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let mut aircraft = aircraft.with_wind(wind.for_aircraft("Mantis"));
+
+    let mut departed = false;
+    let mut max_cross_track_m: f64 = 0.0;
+    let mut max_crab_deg: f64 = 0.0;
+    for _ in 0..10_000 {
+        aircraft.advance();
+        aircraft.step().await;
+
+        let state = &aircraft.state;
+        departed |= state.current_plan.is_some();
+        if departed && state.current_plan.is_none() {
+            break;
+        }
+
+        // on the eastbound leg, clear of the turn at the waypoint
+        let last_leg = state.current_plan.as_ref().is_some_and(|p| p.path.len() == 1);
+        let clear = point!(x: state.position.longitude, y: state.position.latitude)
+            .haversine_distance(&point!(x: WAYPOINT.1, y: WAYPOINT.0))
+            > 100.0;
+        if last_leg && clear && state.activity.is_airborne() {
+            let cross_track = point!(x: state.position.longitude, y: state.position.latitude)
+                .haversine_distance(&point!(x: state.position.longitude, y: WAYPOINT.0));
+            max_cross_track_m = max_cross_track_m.max(cross_track);
+
+            if state.ground_velocity_m_s > 5.0 {
+                let crab = (state.heading_deg - state.track_angle_deg + 540.0).rem_euclid(360.0) - 180.0;
+                max_crab_deg = max_crab_deg.max(crab.abs());
+            }
+        }
+    }
+
+    assert!(max_cross_track_m < 15.0, "drifted {max_cross_track_m} m off the leg");
+    assert!(max_crab_deg > 10.0, "crabbed at most {max_crab_deg} deg");
+
+    // delivered at the destination despite the drift
+    let records = backend.records();
+    assert_eq!(records.scans.len(), 3);
+    assert!(distance_m(DESTINATION, &records.scans[2]) < 10.0);
+}
+
+#[tokio::test]
+async fn declines_plans_beyond_the_performance_profile() {
+    // the plan carries 2 kg
+    let light = PerformanceProfile {
+        payload_capacity_kg: 1.0,
+        ..Default::default()
+    };
+
+    let start = Utc::now();
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let mut aircraft = aircraft.with_profile(light);
+    for _ in 0..200 {
+        aircraft.advance();
+        aircraft.step().await;
+    }
+
+    assert!(aircraft.state.current_plan.is_none());
+    assert_eq!(aircraft.state.activity, Activity::Idle);
+    assert_eq!(backend.records().declined, vec![FLIGHT_UUID.to_string()]);
+}
+
+#[tokio::test]
+async fn flies_within_the_performance_profile() {
+    let start = Utc::now();
+    let profile = PerformanceProfile {
+        airframe: Airframe::Aeroplane,
+        max_climb_rate_m_s: 1.5,
+        ..Default::default()
+    };
+    let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+    let mut aircraft = aircraft.with_profile(profile);
+
+    let mut departed = false;
+    let mut max_climb_m_s: f64 = 0.0;
+    for _ in 0..10_000 {
+        aircraft.advance();
+        aircraft.step().await;
+
+        let state = &aircraft.state;
+        max_climb_m_s = max_climb_m_s.max(state.vertical_velocity_m_s);
+        departed |= state.current_plan.is_some();
+        if departed && state.current_plan.is_none() {
+            break;
+        }
+    }
+
+    assert!(max_climb_m_s <= 1.5 + 1e-9, "climbed at {max_climb_m_s} m/s");
+
+    let records = backend.records();
+    assert!(records.declined.is_empty());
+    assert_eq!(records.scans.len(), 3);
+    let ids: Vec<String> = records
+        .frames
+        .iter()
+        .filter_map(|f| decode_frame(&f.payload).ok())
+        .filter(|(kind, _)| *kind == FrameKind::Basic)
+        .map(|(_, message)| message)
+        .collect();
+    assert!(!ids.is_empty());
+    assert!(ids.iter().all(|message| message.contains("ua_type: Aeroplane")), "{:?}", ids[0]);
+}
+
+#[tokio::test]
+async fn flies_plans_due_far_ahead_at_the_cruise_speed() {
+    let start = Utc::now();
+    let mut plan = flight_plan(start);
+    plan.target_timeslot_start = start + Duration::hours(2);
+    plan.target_timeslot_end = start + Duration::hours(2) + Duration::seconds(30);
+    let profile = PerformanceProfile::default();
+    let (backend, aircraft) = scenario(start, vec![plan]);
+    let mut aircraft = aircraft.with_profile(profile);
+
+    let mut departed = false;
+    let mut arrived = false;
+    for _ in 0..10_000 {
+        aircraft.advance();
+        aircraft.step().await;
+
+        let state = &aircraft.state;
+        if departed && state.current_plan.is_none() {
+            arrived = true;
+            break;
+        }
+        if state.current_plan.is_some() {
+            departed = true;
+            assert_eq!(state.target_ground_velocity_m_s, profile.cruise_speed_m_s);
+        }
+    }
+
+    // well within the endurance, long before the timeslot
+    assert!(arrived);
+    assert!(backend.records().declined.is_empty());
+}
+
+#[tokio::test]
+async fn lands_in_place_when_a_plan_ends_in_the_air() {
+    // slow enough to stop right where the plan ends
+    let start = Utc::now();
+    let mut plan = flight_plan(start);
+    plan.target_timeslot_start = start + Duration::seconds(400);
+    plan.target_timeslot_end = start + Duration::seconds(430);
+    let (_, aircraft) = scenario(start, vec![plan]);
+    let mut aircraft = aircraft.with_profile(PerformanceProfile {
+        cruise_speed_m_s: 5.0,
+        ..Default::default()
+    });
+    step_until(&mut aircraft, 10_000, climbed_out).await;
+
+    // the rest of the path is gone, the plan ends on the next tick
+    let plan = aircraft.state.current_plan.as_mut().expect("flying a plan");
+    plan.path.clear();
+    let (latitude, longitude) = (aircraft.state.position.latitude, aircraft.state.position.longitude);
+
+    let mut phases = vec![];
+    for _ in 0..2_000 {
+        aircraft.advance();
+        aircraft.step().await;
+
+        if phases.last() != Some(&aircraft.state.activity) {
+            phases.push(aircraft.state.activity);
+        }
+        if aircraft.state.activity == Activity::Idle {
+            break;
+        }
+    }
+
+    assert_eq!(phases, vec![Activity::Landing, Activity::Idle]);
+    assert!(aircraft.state.current_plan.is_none());
+    assert_eq!(aircraft.state.position.altitude_meters, 0.0);
+    let landed = point!(x: aircraft.state.position.longitude, y: aircraft.state.position.latitude);
+    assert!(landed.haversine_distance(&point!(x: longitude, y: latitude)) < 10.0);
+}
+
+#[tokio::test]
+async fn lands_in_place_on_shutdown_in_hold() {
+    for mode in [ShutdownMode::Fly, ShutdownMode::Teleport] {
+        let start = Utc::now();
+        let (backend, aircraft) = scenario(start, vec![flight_plan(start)]);
+        let (signal, rx) = watch::channel(false);
+        let options = ShutdownOptions {
+            mode,
+            ..Default::default()
+        };
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let mut aircraft = aircraft
+            .with_shutdown(Shutdown { signal: rx, options })
+            .with_commands(command_rx);
+        step_until(&mut aircraft, 10_000, |state| state.position.altitude_meters > 20.0).await;
+
+        // abort the plan in the air, the aircraft holds without one
+        commands.send(Command::AbortPlan).expect("aircraft takes commands");
+        aircraft.advance();
+        aircraft.step().await;
+        assert_eq!(aircraft.state.activity, Activity::Hold);
+        assert!(aircraft.state.current_plan.is_none());
+
+        signal.send_replace(true);
+        let report = aircraft.run(TICK_MS, StatusBoard::default()).await;
+
+        let expected = match mode {
+            ShutdownMode::Fly => Landing::InPlace,
+            ShutdownMode::Teleport => Landing::Teleported,
+        };
+        assert_eq!(report.landing, expected);
+        assert_eq!(report.position.altitude_meters, 0.0);
+
+        // the backend last saw the aircraft on the ground
+        let records = backend.records();
+        let last = records.frames.last().expect("frames were sent");
+        let (kind, message) = decode_frame(&last.payload).expect("final frame should decode");
+        assert_eq!(kind, FrameKind::Location);
+        assert!(message.contains("operational_status: Ground"), "final frame: {message}");
+    }
+}
+
+/// A telemetry service that never answers
+struct Hung;
+
+impl TelemetrySink for Hung {
+    async fn acquire_token(&self, _identifier: &str) -> Result<String, BackendError> {
+        std::future::pending().await
+    }
+
+    async fn send_frame(&self, _token: &str, _frame: &TelemetryFrame) -> Result<(), BackendError> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn opens_the_circuit_breaker_of_a_hung_service() {
+    let policy = RetryPolicy {
+        breaker_threshold: 2,
+        ..Default::default()
+    };
+    let options = LinkOptions {
+        telemetry_timeout_ms: 20,
+        ..Default::default()
+    };
+    let sink = Guarded::new(Hung, Breakers::new(policy)).with_timeouts(options);
+
+    // timeouts count as failures until the breaker opens
+    for _ in 0..2 {
+        let result = sink.acquire_token("Mantis").await;
+        assert!(matches!(result, Err(BackendError::Timeout { endpoint: "login" })), "{result:?}");
+    }
+
+    let result = sink.acquire_token("Mantis").await;
+    assert!(matches!(result, Err(BackendError::CircuitOpen { endpoint: "login" })), "{result:?}");
+}